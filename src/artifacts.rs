@@ -1,23 +1,44 @@
 //! `$ mlmdquery {get,count} artifacts` implementation.
-use crate::serialize::Artifact;
+use crate::contexts::resolve_context_name;
+use crate::db::{fetch_page, DbOpt, ID_CHUNK_SIZE};
+use crate::error::NotFoundError;
+use crate::events::{related_artifact_ids, EventDirection};
+use crate::graph::TimeZone;
+use crate::query::{
+    count_needs_fetch, dedup_ids, explain_filters, paginate, shuffle, sort_by_id_order,
+    FilterExplanation, FilterMode, JsonPropertyFilter, LikePattern, NonFinitePolicy, Page,
+    PropertyFilter, Regex, TimePoint, TimeUnit,
+};
+use crate::serialize::{
+    coerce_properties, handle_nonfinite, state_name, Artifact, ArtifactState, ExecutionSummary,
+    PropertyType, PropertyValue, Type,
+};
+use anyhow::Context as _;
 use std::collections::{BTreeMap, BTreeSet};
-use std::time::Duration;
+use std::path::{Path, PathBuf};
 
 /// `$ mlmdquery {get,count} artifacts` common options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "kebab-case")]
 pub struct CommonArtifactsOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    #[serde(skip)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub db: DbOpt,
 
     /// Target artifact IDs.
     #[structopt(long = "id")]
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub ids: Vec<i32>,
 
+    /// File containing newline-separated target artifact IDs (`-` means stdin).
+    ///
+    /// The IDs are queried in chunks and the results are merged after fetching them from the DB.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_file: Option<PathBuf>,
+
     /// Target artifact name.
     #[structopt(long, requires("type-name"))]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -28,7 +49,17 @@ pub struct CommonArtifactsOpt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub name_pattern: Option<String>,
 
+    /// Target artifact name regular expression (e.g., `^model-v[0-9]+$`).
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB (narrow the candidates with `--type` if possible).
+    /// `--limit` and `--offset` are applied to the filtered result.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name_regex: Option<Regex>,
+
     /// Target artifact type.
+    ///
+    /// A value prefixed with `id:` (e.g., `id:42`) is interpreted as a type ID instead of a type name.
     #[structopt(long = "type")]
     #[serde(rename = "type")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -39,44 +70,358 @@ pub struct CommonArtifactsOpt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub uri: Option<String>,
 
+    /// Target artifact URI prefix (e.g., "s3://bucket/path/").
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long, conflicts_with("uri"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri_prefix: Option<String>,
+
+    /// Target artifact URI pattern (SQL LIKE statement value, e.g., "s3://bucket/run-42/%").
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long, conflicts_with("uri"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uri_pattern: Option<LikePattern>,
+
     /// Context ID to which target artifacts belong.
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub context: Option<i32>,
 
-    /// Start of creation time (UNIX timestamp seconds).
+    /// Name of the context to which target artifacts belong.
+    ///
+    /// The name is resolved into a context ID before querying artifacts.
+    /// If multiple contexts have the name, `--context-type` is required to disambiguate them.
+    #[structopt(long, conflicts_with("context"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_name: Option<String>,
+
+    /// Type name of the context specified by `--context-name`.
+    #[structopt(long, requires("context-name"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_type: Option<String>,
+
+    /// Execution ID that produced target artifacts (i.e., via `OUTPUT` events).
+    #[structopt(long, conflicts_with("id-file"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub produced_by: Option<i32>,
+
+    /// Execution ID that consumed target artifacts (i.e., via `INPUT` events).
+    #[structopt(long, conflicts_with("id-file"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consumed_by: Option<i32>,
+
+    /// Execution type name whose most recently created execution produced target artifacts.
+    ///
+    /// E.g., `--latest-of-execution-type Trainer --type Model` returns the model of the latest training run.
+    #[structopt(long, conflicts_with("id-file"))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_of_execution_type: Option<String>,
+
+    /// Start of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_start: Option<f64>,
+    pub ctime_start: Option<TimePoint>,
 
-    /// End of creation time (UNIX timestamp seconds).
+    /// End of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_end: Option<f64>,
+    pub ctime_end: Option<TimePoint>,
 
-    /// Start of update time (UNIX timestamp seconds).
+    /// Start of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
+    #[structopt(long, visible_alias = "mtime-since")]
+    #[serde(alias = "mtime-since")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime_start: Option<TimePoint>,
+
+    /// End of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_start: Option<f64>,
+    pub mtime_end: Option<TimePoint>,
 
-    /// End of update time (UNIX timestamp seconds).
+    /// Time zone in which the date strings given to `--{c,m}time-{start,end}` are interpreted
+    /// (unless they contain an offset).
+    #[structopt(long, default_value = "utc", possible_values = TimeZone::POSSIBLE_VALUES, case_insensitive = true)]
+    #[serde(default = "TimeZone::utc")]
+    pub input_timezone: TimeZone,
+
+    /// Target artifact URI scheme (e.g., "s3").
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_end: Option<f64>,
+    pub uri_scheme: Option<String>,
+
+    /// Condition on a value inside a JSON string property (`NAME:POINTER OP VALUE`, can be specified multiple times).
+    ///
+    /// For example, `metrics:/accuracy>0.9` returns the artifacts whose `metrics` property is a JSON string
+    /// having a number greater than 0.9 at `/accuracy` (JSON Pointer). OP is one of `=`, `!=`, `<`, `<=`, `>` and `>=`,
+    /// and VALUE is parsed as JSON if possible. The artifacts whose property is missing or not valid JSON are excluded.
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long = "json-property-filter")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub json_property_filters: Vec<JsonPropertyFilter>,
+
+    /// Target artifact states (can be specified multiple times).
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long, possible_values = ArtifactState::POSSIBLE_VALUES, case_insensitive = true)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub state: Vec<ArtifactState>,
+
+    /// Target artifact property values (`NAME=VALUE`, can be specified multiple times).
+    ///
+    /// Only the artifacts having all the given values in `properties` are returned.
+    /// VALUE is parsed as an int, a double or a string (in that order), and VALUE prefixed with `@`
+    /// is read from the file at the following path (e.g., `config_hash=@hash.txt`).
+    /// Use `--custom-property` to filter on `custom_properties`.
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long = "property")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub properties: Vec<PropertyFilter>,
+
+    /// Target artifact custom property values (`NAME=VALUE`, can be specified multiple times).
+    ///
+    /// Only the artifacts having all the given values in `custom_properties` are returned.
+    /// VALUE is parsed in the same way as `--property`.
+    ///
+    /// Note that this filter is applied after fetching artifacts from the DB.
+    #[structopt(long = "custom-property")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_properties: Vec<PropertyFilter>,
 }
 
 impl CommonArtifactsOpt {
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        use FilterMode::*;
+
+        explain_filters(&[
+            ("--id", !self.ids.is_empty(), ServerSide),
+            ("--id-file", self.id_file.is_some(), ClientSide),
+            ("--name", self.name.is_some(), ServerSide),
+            ("--name-pattern", self.name_pattern.is_some(), ServerSide),
+            ("--name-regex", self.name_regex.is_some(), ClientSide),
+            ("--type", self.type_name.is_some(), ServerSide),
+            ("--uri", self.uri.is_some(), ServerSide),
+            ("--uri-prefix", self.uri_prefix.is_some(), ClientSide),
+            ("--uri-pattern", self.uri_pattern.is_some(), ClientSide),
+            ("--context", self.context.is_some(), ServerSide),
+            // Resolved into a context ID before querying artifacts.
+            ("--context-name", self.context_name.is_some(), ServerSide),
+            // Resolved into artifact IDs before querying artifacts.
+            ("--produced-by", self.produced_by.is_some(), ServerSide),
+            ("--consumed-by", self.consumed_by.is_some(), ServerSide),
+            (
+                "--latest-of-execution-type",
+                self.latest_of_execution_type.is_some(),
+                ServerSide,
+            ),
+            ("--ctime-start", self.ctime_start.is_some(), ServerSide),
+            ("--ctime-end", self.ctime_end.is_some(), ServerSide),
+            ("--mtime-start", self.mtime_start.is_some(), ServerSide),
+            ("--mtime-end", self.mtime_end.is_some(), ServerSide),
+            ("--uri-scheme", self.uri_scheme.is_some(), ClientSide),
+            (
+                "--json-property-filter",
+                !self.json_property_filters.is_empty(),
+                ClientSide,
+            ),
+            ("--state", !self.state.is_empty(), ClientSide),
+            ("--property", !self.properties.is_empty(), ClientSide),
+            (
+                "--custom-property",
+                !self.custom_properties.is_empty(),
+                ClientSide,
+            ),
+        ])
+    }
+
+    fn has_client_side_filters(&self) -> bool {
+        self.explain()
+            .iter()
+            .any(|x| x.mode != FilterMode::ServerSide)
+    }
+
+    fn matches(&self, artifact: &mlmd::metadata::Artifact) -> bool {
+        if let Some(scheme) = &self.uri_scheme {
+            if !matches!(uri_scheme(artifact), Some(x) if x.eq_ignore_ascii_case(scheme)) {
+                return false;
+            }
+        }
+        if let Some(prefix) = &self.uri_prefix {
+            if !matches!(&artifact.uri, Some(x) if x.starts_with(prefix.as_str())) {
+                return false;
+            }
+        }
+        if let Some(pattern) = &self.uri_pattern {
+            if !matches!(&artifact.uri, Some(x) if pattern.is_match(x)) {
+                return false;
+            }
+        }
+        if let Some(regex) = &self.name_regex {
+            if !matches!(&artifact.name, Some(x) if regex.is_match(x)) {
+                return false;
+            }
+        }
+        if !self
+            .json_property_filters
+            .iter()
+            .all(|x| x.matches(&artifact.properties, &artifact.custom_properties))
+        {
+            return false;
+        }
+        if !self.state.is_empty() && !self.state.contains(&artifact.state.into()) {
+            return false;
+        }
+        if !self
+            .properties
+            .iter()
+            .all(|x| x.matches(&artifact.properties))
+        {
+            return false;
+        }
+        if !self
+            .custom_properties
+            .iter()
+            .all(|x| x.matches(&artifact.custom_properties))
+        {
+            return false;
+        }
+        true
+    }
+
+    // Returns a copy of the options in which an `id:<ID>` type is replaced with the type name,
+    // `--context-name` is replaced with the context ID, and `--produced-by` / `--consumed-by` / `--latest-of-execution-type` are replaced with
+    // the artifact IDs they refer to.
+    //
+    // Returns `None` if no artifacts can match the options.
+    async fn resolve(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Option<Self>> {
+        let mut resolved = self.clone();
+        if let Some(id) = self.type_name.as_ref().and_then(|x| x.strip_prefix("id:")) {
+            let id = id
+                .parse()
+                .with_context(|| format!("invalid artifact type ID: {:?}", id))?;
+            let mut types = store
+                .get_artifact_types()
+                .ids(std::iter::once(mlmd::metadata::TypeId::new(id)))
+                .execute()
+                .await?;
+            anyhow::ensure!(
+                types.len() == 1,
+                NotFoundError::new(format!("No such artifact type: {}", id))
+            );
+            resolved.type_name = Some(types.remove(0).name);
+        }
+        if let Some(name) = &self.context_name {
+            let id = resolve_context_name(store, name, self.context_type.as_deref()).await?;
+            resolved.context = Some(id.get());
+            resolved.context_name = None;
+            resolved.context_type = None;
+        }
+
+        let mut candidates = if self.ids.is_empty() {
+            None
+        } else {
+            Some(self.ids.iter().copied().collect::<BTreeSet<_>>())
+        };
+        let latest = if let Some(ty) = &self.latest_of_execution_type {
+            let executions = store
+                .get_executions()
+                .ty(ty)
+                .order_by(mlmd::requests::ExecutionOrderByField::CreateTime, false)
+                .limit(1)
+                .execute()
+                .await?;
+            if let Some(execution) = executions.first() {
+                Some(execution.id.get())
+            } else {
+                return Ok(None);
+            }
+        } else {
+            None
+        };
+        for (execution, direction) in [
+            (self.produced_by, EventDirection::Output),
+            (self.consumed_by, EventDirection::Input),
+            (latest, EventDirection::Output),
+        ] {
+            if let Some(execution) = execution {
+                let ids = related_artifact_ids(store, execution, direction).await?;
+                candidates = Some(match candidates {
+                    None => ids,
+                    Some(x) => x.intersection(&ids).copied().collect(),
+                });
+            }
+        }
+        if self.produced_by.is_some() || self.consumed_by.is_some() || latest.is_some() {
+            match candidates {
+                Some(x) if !x.is_empty() => {
+                    resolved.ids = x.into_iter().collect();
+                    resolved.produced_by = None;
+                    resolved.consumed_by = None;
+                    resolved.latest_of_execution_type = None;
+                }
+                _ => return Ok(None),
+            }
+        }
+        Ok(Some(resolved))
+    }
+
+    async fn execute(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        order_by: ArtifactOrderByField,
+        asc: bool,
+    ) -> anyhow::Result<Vec<mlmd::metadata::Artifact>> {
+        let artifacts = if let Some(path) = &self.id_file {
+            let mut ids = read_ids(path)?;
+            ids.extend(self.ids.iter().copied());
+            ids.sort_unstable();
+            ids.dedup();
+
+            let mut artifacts = Vec::new();
+            for chunk in ids.chunks(ID_CHUNK_SIZE) {
+                artifacts.extend(self.request_with_ids(store, chunk)?.execute().await?);
+            }
+            artifacts.sort_by(|a, b| {
+                let ordering = order_by.compare(a, b);
+                if asc {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+            artifacts
+        } else {
+            self.request(store)?
+                .order_by(order_by.into(), asc)
+                .execute()
+                .await?
+        };
+        Ok(artifacts.into_iter().filter(|x| self.matches(x)).collect())
+    }
+
     fn request<'a>(
         &self,
         store: &'a mut mlmd::MetadataStore,
-    ) -> mlmd::requests::GetArtifactsRequest<'a> {
+    ) -> anyhow::Result<mlmd::requests::GetArtifactsRequest<'a>> {
+        self.request_with_ids(store, &self.ids)
+    }
+
+    fn request_with_ids<'a>(
+        &self,
+        store: &'a mut mlmd::MetadataStore,
+        ids: &[i32],
+    ) -> anyhow::Result<mlmd::requests::GetArtifactsRequest<'a>> {
         let mut request = store.get_artifacts();
 
-        if !self.ids.is_empty() {
+        if !ids.is_empty() {
             request = request.ids(
-                self.ids
-                    .iter()
-                    .copied()
+                dedup_ids(ids)
+                    .into_iter()
                     .map(mlmd::metadata::ArtifactId::new),
             );
         }
@@ -98,24 +443,25 @@ impl CommonArtifactsOpt {
         if let Some(x) = self.context {
             request = request.context(mlmd::metadata::ContextId::new(x));
         }
-        request = match (self.ctime_start, self.ctime_end) {
+        let time = |x: &Option<TimePoint>| {
+            x.as_ref()
+                .map(|x| x.to_duration(self.input_timezone))
+                .transpose()
+        };
+        request = match (time(&self.ctime_start)?, time(&self.ctime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.create_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.create_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.create_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.create_time(s..),
+            (None, Some(e)) => request.create_time(..e),
+            (Some(s), Some(e)) => request.create_time(s..e),
         };
-        request = match (self.mtime_start, self.mtime_end) {
+        request = match (time(&self.mtime_start)?, time(&self.mtime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.update_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.update_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.update_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.update_time(s..),
+            (None, Some(e)) => request.update_time(..e),
+            (Some(s), Some(e)) => request.update_time(s..e),
         };
 
-        request
+        Ok(request)
     }
 }
 
@@ -130,10 +476,40 @@ pub enum ArtifactOrderByField {
     CreateTime,
     #[serde(rename = "mtime")]
     UpdateTime,
+    Random,
+}
+
+impl ArtifactOrderByField {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["id", "name", "ctime", "mtime", "random"];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::CreateTime => "ctime",
+            Self::UpdateTime => "mtime",
+            Self::Random => "random",
+        }
+    }
 }
 
 impl ArtifactOrderByField {
-    const POSSIBLE_VALUES: &'static [&'static str] = &["id", "name", "ctime", "mtime"];
+    fn compare(
+        self,
+        a: &mlmd::metadata::Artifact,
+        b: &mlmd::metadata::Artifact,
+    ) -> std::cmp::Ordering {
+        let ordering = match self {
+            Self::Id => a.id.cmp(&b.id),
+            Self::Name => a.name.cmp(&b.name),
+            Self::CreateTime => a.create_time_since_epoch.cmp(&b.create_time_since_epoch),
+            Self::UpdateTime => a
+                .last_update_time_since_epoch
+                .cmp(&b.last_update_time_since_epoch),
+            Self::Random => std::cmp::Ordering::Equal,
+        };
+        ordering.then_with(|| a.id.cmp(&b.id))
+    }
 }
 
 impl Default for ArtifactOrderByField {
@@ -151,11 +527,69 @@ impl std::str::FromStr for ArtifactOrderByField {
             "name" => Ok(Self::Name),
             "ctime" => Ok(Self::CreateTime),
             "mtime" => Ok(Self::UpdateTime),
+            "random" => Ok(Self::Random),
             _ => anyhow::bail!("invalid value: {:?}", s),
         }
     }
 }
 
+/// Fields that can be used to group counted artifacts.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(missing_docs)]
+pub enum ArtifactGroupByField {
+    UriScheme,
+    State,
+}
+
+impl ArtifactGroupByField {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["uri-scheme", "state"];
+
+    fn key(self, artifact: &mlmd::metadata::Artifact) -> String {
+        match self {
+            Self::UriScheme => uri_scheme(artifact).unwrap_or("").to_owned(),
+            Self::State => state_name(ArtifactState::from(artifact.state)),
+        }
+    }
+}
+
+impl std::str::FromStr for ArtifactGroupByField {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "uri-scheme" => Ok(Self::UriScheme),
+            "state" => Ok(Self::State),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+fn read_ids(path: &Path) -> anyhow::Result<Vec<i32>> {
+    let text = if path == Path::new("-") {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?
+    };
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .with_context(|| format!("invalid artifact ID: {:?}", line))
+        })
+        .collect()
+}
+
+fn uri_scheme(artifact: &mlmd::metadata::Artifact) -> Option<&str> {
+    artifact
+        .uri
+        .as_ref()
+        .and_then(|uri| uri.split_once("://"))
+        .map(|(scheme, _)| scheme)
+}
+
 impl From<ArtifactOrderByField> for mlmd::requests::ArtifactOrderByField {
     fn from(x: ArtifactOrderByField) -> Self {
         match x {
@@ -163,25 +597,135 @@ impl From<ArtifactOrderByField> for mlmd::requests::ArtifactOrderByField {
             ArtifactOrderByField::Name => Self::Name,
             ArtifactOrderByField::CreateTime => Self::CreateTime,
             ArtifactOrderByField::UpdateTime => Self::UpdateTime,
+            // The DB cannot randomize the order, so the result is shuffled on the client side.
+            ArtifactOrderByField::Random => Self::Id,
         }
     }
 }
 
 /// `$ mlmdquery count artifacts` options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct CountArtifactsOpt {
     /// Common options.
     #[structopt(flatten)]
     #[serde(flatten)]
     pub common: CommonArtifactsOpt,
+
+    /// If specified, the artifacts are counted per value of the field.
+    #[structopt(long, possible_values = ArtifactGroupByField::POSSIBLE_VALUES)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<ArtifactGroupByField>,
+
+    /// If specified, the artifacts are counted per state (`{"LIVE": N, "DELETED": M, ...}`).
+    ///
+    /// This is a shorthand for `--group-by state`.
+    #[structopt(long, conflicts_with("group-by"))]
+    #[serde(default)]
+    pub count_by_state: bool,
+
+    /// If specified, the artifacts are counted per context they belong to (`{"<context ID>": N, ...}`).
+    ///
+    /// An artifact belonging to multiple contexts is counted in each of them,
+    /// and the artifacts not belonging to any context are not counted.
+    #[structopt(long, conflicts_with_all(&["group-by", "count-by-state"]))]
+    #[serde(default)]
+    pub per_context: bool,
 }
 
 impl CountArtifactsOpt {
+    /// Returns the field specified by `--group-by` or `--count-by-state`.
+    pub fn group_by_field(&self) -> Option<ArtifactGroupByField> {
+        if self.count_by_state {
+            Some(ArtifactGroupByField::State)
+        } else {
+            self.group_by
+        }
+    }
+
     /// `$ mlmdquery count artifacts` implementation.
     pub async fn count(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<usize> {
-        let n = self.common.request(store).count().await?;
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(0);
+        };
+        if count_needs_fetch(&common.explain()) {
+            let artifacts = common
+                .execute(store, ArtifactOrderByField::Id, true)
+                .await?;
+            return Ok(artifacts.len());
+        }
+        let n = common.request(store)?.count().await?;
         Ok(n)
     }
+
+    /// `$ mlmdquery count artifacts --per-context` implementation.
+    pub async fn count_per_context(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(counts);
+        };
+        let artifact_ids = common
+            .execute(store, ArtifactOrderByField::Id, true)
+            .await?
+            .into_iter()
+            .map(|x| x.id)
+            .collect::<BTreeSet<_>>();
+
+        let artifact_ids = artifact_ids.into_iter().collect::<Vec<_>>();
+
+        let mut context_ids = BTreeSet::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let contexts = store
+                .get_contexts()
+                .artifacts(chunk.iter().copied())
+                .execute()
+                .await?;
+            context_ids.extend(contexts.into_iter().map(|x| x.id));
+        }
+
+        // Only the matching artifacts are counted (a context can have far more members than them).
+        for context_id in context_ids {
+            let mut n = 0;
+            for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+                n += store
+                    .get_artifacts()
+                    .context(context_id)
+                    .ids(chunk.iter().copied())
+                    .count()
+                    .await?;
+            }
+            counts.insert(context_id.get().to_string(), n);
+        }
+        Ok(counts)
+    }
+
+    /// `$ mlmdquery count artifacts --group-by` implementation.
+    pub async fn count_groups(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        group_by: ArtifactGroupByField,
+    ) -> anyhow::Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(counts);
+        };
+        let artifacts = common
+            .execute(store, ArtifactOrderByField::Id, true)
+            .await?;
+        for artifact in artifacts {
+            *counts.entry(group_by.key(&artifact)).or_default() += 1;
+        }
+        Ok(counts)
+    }
 }
 
 /// `$ mlmdquery get artifacts` options.
@@ -193,17 +737,48 @@ pub struct GetArtifactsOpt {
     pub common: CommonArtifactsOpt,
 
     /// Field to be used to sort a search result.
+    ///
+    /// `random` shuffles the result on the client side, so all the matching artifacts are fetched
+    /// from the DB before `--limit` and `--offset` are applied (this may be slow for a large DB).
     #[structopt(long, default_value="id", possible_values = ArtifactOrderByField::POSSIBLE_VALUES)]
     #[serde(default)]
     pub order_by: ArtifactOrderByField,
 
+    /// Property name (`properties` or `custom_properties`) by which the search result is sorted (overriding `--order-by`).
+    ///
+    /// Numbers are compared numerically and strings lexicographically (numbers come first).
+    /// The artifacts without the property are placed last regardless of `--asc`, and ties are broken by ID.
+    /// Note that all the matching artifacts are fetched from the DB before `--limit` and `--offset` are applied.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order_by_property: Option<String>,
+
+    /// Seed of the random number generator used by `--order-by random`.
+    ///
+    /// If omitted, a different order is produced on each run.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// If specified, the search results are sorted in the order of the `--id` arguments (overriding `--order-by`).
+    ///
+    /// Note that all the matching artifacts are fetched from the DB before `--limit` and `--offset` are applied.
+    #[structopt(long, requires("ids"))]
+    #[serde(default)]
+    pub preserve_order: bool,
+
     /// If specified, the search results will be sorted in ascending order.
     #[structopt(long)]
     #[serde(default)]
     pub asc: bool,
 
-    /// Maximum number of artifacts in a search result.
-    #[structopt(long, default_value = "100")]
+    /// If specified, the search results will be sorted in descending order (default).
+    #[structopt(long, conflicts_with("asc"))]
+    #[serde(default)]
+    pub desc: bool,
+
+    /// Maximum number of artifacts in a search result (0 means unlimited).
+    #[structopt(long, env = "MLMD_LIMIT_ARTIFACTS", default_value = "100")]
     #[serde(default = "GetArtifactsOpt::limit_default")]
     pub limit: usize,
 
@@ -211,36 +786,485 @@ pub struct GetArtifactsOpt {
     #[structopt(long, default_value = "0")]
     #[serde(default)]
     pub offset: usize,
+
+    /// If specified, `properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_properties: bool,
+
+    /// If specified, `custom_properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_custom_properties: bool,
+
+    /// If specified, property values are converted to the types declared in their type schema.
+    #[structopt(long)]
+    #[serde(default)]
+    pub coerce_to_schema: bool,
+
+    /// If specified, property values that cannot be converted to their declared types cause an error.
+    #[structopt(long, requires("coerce-to-schema"))]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Unit of the timestamps (`ctime` and `mtime`) and of `mtime_delta` in the output.
+    #[structopt(long, default_value = "s", possible_values = TimeUnit::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub time_unit: TimeUnit,
+
+    /// How to output non-finite (`NaN` or infinite) double property values.
+    ///
+    /// `null` outputs `null`, `string` outputs "NaN", "Infinity" or "-Infinity", and `error` fails the query.
+    #[structopt(long, default_value = "null", possible_values = NonFinitePolicy::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub nonfinite: NonFinitePolicy,
+
+    /// If specified, `undeclared_properties` (the property names not declared in the artifact type) is added to each artifact.
+    #[structopt(long)]
+    #[serde(default)]
+    pub report_undeclared_properties: bool,
+
+    /// If specified, `mtime_delta` (the time elapsed from `ctime` to `mtime`) is added to each artifact.
+    #[structopt(long)]
+    #[serde(default)]
+    pub with_mtime_delta: bool,
+
+    /// If specified, `producer` (the ID, type and name of the execution that output the artifact) is added to each artifact.
+    ///
+    /// `producer` is `null` if there is no such execution, and the most recent one is used if there are multiple.
+    #[structopt(long)]
+    #[serde(default)]
+    pub with_producer_execution: bool,
+
+    /// If specified, the artifacts are grouped by the contexts they belong to (`{"<context ID>": [artifact, ...], ...}`).
+    ///
+    /// An artifact belonging to multiple contexts appears in each group,
+    /// and the artifacts not belonging to any context are omitted.
+    #[structopt(long)]
+    #[serde(default)]
+    pub group_into_contexts: bool,
+
+    /// Property name (`properties` or `custom_properties`) by which the artifacts are deduplicated.
+    ///
+    /// Only the most recently created artifact is kept for each distinct value of the property.
+    /// Note that this is applied after fetching all the matching artifacts from the DB.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dedup_by: Option<String>,
+
+    /// How to handle the artifacts without the `--dedup-by` property.
+    ///
+    /// `group` treats them as a group having the same value and `drop` excludes them from the result.
+    #[structopt(long, default_value = "group", possible_values = DedupMissing::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub dedup_missing: DedupMissing,
+}
+
+/// How to handle the artifacts without the property specified by `--dedup-by`.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+pub enum DedupMissing {
+    #[default]
+    Group,
+    Drop,
+}
+
+impl DedupMissing {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["group", "drop"];
+}
+
+impl std::str::FromStr for DedupMissing {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "group" => Ok(Self::Group),
+            "drop" => Ok(Self::Drop),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
 }
 
 impl GetArtifactsOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery get artifacts --type DataSet\n",
+        "    $ mlmdquery get artifacts --type DataSet --name-pattern 'mnist-%'\n",
+        "    $ mlmdquery get artifacts --type Model --name-regex '^model-v[0-9]+(-rc[0-9]+)?$'\n",
+        "    $ mlmdquery get artifacts --uri path/to/data\n",
+        "    $ mlmdquery get artifacts --type Model --json-property-filter 'metrics:/accuracy>0.9'\n",
+        "    $ mlmdquery get artifacts --type Model --state LIVE --state PENDING\n",
+        "    $ mlmdquery get artifacts --type DataSet --property split=train\n",
+        "    $ mlmdquery get artifacts --ctime-start 1628605220 --ctime-end 1628605300 --asc\n",
+        "    $ mlmdquery get artifacts --context 1 --order-by mtime --limit 10\n",
+        "    $ mlmdquery get artifacts --context-name run-42 --context-type PipelineRun\n",
+        "    $ mlmdquery get artifacts --produced-by 1\n",
+        "    $ mlmdquery get artifacts --mtime-since 2021-08-10 --with-mtime-delta\n",
+        "    $ mlmdquery get artifacts --type Model --group-into-contexts\n",
+        "    $ mlmdquery get artifacts --type Model --dedup-by model_name\n",
+        "    $ mlmdquery get artifacts --type Model --with-producer-execution\n",
+        "    $ mlmdquery get artifacts --type Model --latest-of-execution-type Trainer\n",
+        "    $ mlmdquery get artifacts --type DataSet --order-by random --seed 42 --limit 10\n",
+        "    $ mlmdquery get artifacts --type Model --order-by-property accuracy --limit 10\n",
+    );
+
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        let mut filters = self.common.explain();
+        filters.extend(explain_filters(&[
+            (
+                "--dedup-by",
+                self.dedup_by.is_some(),
+                FilterMode::ClientSide,
+            ),
+            (
+                "--order-by random",
+                self.is_random_order(),
+                FilterMode::ClientSide,
+            ),
+            (
+                "--order-by-property",
+                self.order_by_property.is_some(),
+                FilterMode::ClientSide,
+            ),
+            (
+                "--preserve-order",
+                self.preserve_order,
+                FilterMode::ClientSide,
+            ),
+        ]));
+        filters
+    }
+
     fn limit_default() -> usize {
-        100
+        std::env::var("MLMD_LIMIT_ARTIFACTS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100)
     }
 
     /// `$ mlmdquery get artifacts` implementation.
     pub async fn get(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Vec<Artifact>> {
-        let artifacts = self
-            .common
-            .request(store)
-            .limit(self.limit)
-            .offset(self.offset)
-            .order_by(self.order_by.into(), self.asc)
-            .execute()
-            .await?;
+        Ok(self.get_page(store).await?.items)
+    }
+
+    /// Same as [`GetArtifactsOpt::get`], but also tells whether more artifacts than `--limit` existed.
+    pub async fn get_page(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<Page<Artifact>> {
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(Page {
+                items: Vec::new(),
+                truncated: false,
+            });
+        };
+        let random = self.is_random_order();
+        let Page {
+            items: artifacts,
+            truncated,
+        } = if common.has_client_side_filters()
+            || self.dedup_by.is_some()
+            || random
+            || self.order_by_property.is_some()
+            || self.preserve_order
+        {
+            let mut artifacts = common.execute(store, self.order_by, self.asc).await?;
+            if let Some(key) = &self.dedup_by {
+                artifacts = self.dedup(artifacts, key)?;
+            }
+            if random {
+                shuffle(&mut artifacts, self.seed);
+            }
+            if let Some(name) = &self.order_by_property {
+                self.sort_by_property(&mut artifacts, name);
+            }
+            if self.preserve_order {
+                sort_by_id_order(&mut artifacts, &self.common.ids, |x| x.id.get());
+            }
+            paginate(artifacts, self.offset, self.limit)
+        } else {
+            fetch_page(store, self.limit, self.offset, |store, limit, offset| {
+                let request = common.request(store).map(|x| {
+                    x.limit(limit)
+                        .offset(offset)
+                        .order_by(self.order_by.into(), self.asc)
+                });
+                Box::pin(async move { Ok(request?.execute().await?) })
+            })
+            .await?
+        };
 
         let artifact_types = self.get_artifact_types(store, &artifacts).await?;
+        let mut artifacts = artifacts
+            .into_iter()
+            .map(|mut x| {
+                let ty = &artifact_types[&x.type_id];
+                let undeclared_properties = if self.report_undeclared_properties {
+                    Some(
+                        x.properties
+                            .keys()
+                            .chain(x.custom_properties.keys())
+                            .filter(|k| !ty.properties.contains_key(*k))
+                            .cloned()
+                            .collect::<BTreeSet<_>>(),
+                    )
+                } else {
+                    None
+                };
+                let mtime_delta = if self.with_mtime_delta {
+                    Some(
+                        self.time_unit.timestamp(
+                            x.last_update_time_since_epoch
+                                .saturating_sub(x.create_time_since_epoch),
+                        ),
+                    )
+                } else {
+                    None
+                };
+                if self.no_properties {
+                    x.properties.clear();
+                }
+                if self.no_custom_properties {
+                    x.custom_properties.clear();
+                }
+                let mut artifact = Artifact::new(ty.name.clone(), x, self.time_unit);
+                artifact.undeclared_properties = undeclared_properties;
+                artifact.mtime_delta = mtime_delta;
+                if self.coerce_to_schema {
+                    coerce_properties(&mut artifact.properties, &ty.properties, self.strict)
+                        .with_context(|| format!("artifact {}", artifact.id))?;
+                }
+                handle_nonfinite(&mut artifact.properties, self.nonfinite)
+                    .and_then(|()| {
+                        handle_nonfinite(&mut artifact.custom_properties, self.nonfinite)
+                    })
+                    .with_context(|| format!("artifact {}", artifact.id))?;
+                Ok(artifact)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if self.with_producer_execution {
+            let mut producers = self.get_producers(store, &artifacts).await?;
+            for artifact in &mut artifacts {
+                artifact.producer = Some(producers.remove(&artifact.id));
+            }
+        }
+        Ok(Page {
+            items: artifacts,
+            truncated,
+        })
+    }
+
+    // Returns the most recent execution that output each artifact.
+    async fn get_producers(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        artifacts: &[Artifact],
+    ) -> anyhow::Result<BTreeMap<i32, ExecutionSummary>> {
+        let artifact_ids = artifacts
+            .iter()
+            .map(|x| mlmd::metadata::ArtifactId::new(x.id))
+            .collect::<Vec<_>>();
+        let mut latest = BTreeMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let events = store
+                .get_events()
+                .artifacts(chunk.iter().copied())
+                .execute()
+                .await?;
+            for event in events {
+                if EventDirection::of(event.ty) != Some(EventDirection::Output) {
+                    continue;
+                }
+                let candidate = (event.create_time_since_epoch, event.execution_id);
+                let entry = latest.entry(event.artifact_id.get()).or_insert(candidate);
+                if *entry < candidate {
+                    *entry = candidate;
+                }
+            }
+        }
+
+        let execution_ids = latest
+            .values()
+            .map(|&(_, id)| id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut executions = BTreeMap::new();
+        for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+            let request = store.get_executions().ids(chunk.iter().copied());
+            executions.extend(request.execute().await?.into_iter().map(|x| (x.id, x)));
+        }
+        let execution_types = store
+            .get_execution_types()
+            .ids(
+                executions
+                    .values()
+                    .map(|x| x.type_id)
+                    .collect::<BTreeSet<_>>()
+                    .into_iter(),
+            )
+            .execute()
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x.name))
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(latest
+            .into_iter()
+            .filter_map(|(artifact_id, (_, execution_id))| {
+                let execution = executions.get(&execution_id)?;
+                let producer = ExecutionSummary {
+                    id: execution_id.get(),
+                    type_name: execution_types[&execution.type_id].clone(),
+                    name: execution.name.clone(),
+                };
+                Some((artifact_id, producer))
+            })
+            .collect())
+    }
+
+    // Returns whether the artifacts are shuffled (`--order-by-property` takes precedence over `--order-by random`).
+    fn is_random_order(&self) -> bool {
+        self.order_by_property.is_none() && matches!(self.order_by, ArtifactOrderByField::Random)
+    }
+
+    // Sorts the artifacts by the value of the `name` property (artifacts without it come last).
+    //
+    // Ties are broken by ascending ID regardless of `--asc`.
+    fn sort_by_property(&self, artifacts: &mut [mlmd::metadata::Artifact], name: &str) {
+        use mlmd::metadata::PropertyValue as V;
+        use std::cmp::Ordering;
+
+        fn value<'a>(x: &'a mlmd::metadata::Artifact, name: &str) -> Option<&'a V> {
+            x.properties
+                .get(name)
+                .or_else(|| x.custom_properties.get(name))
+        }
+        let compare = |a: &V, b: &V| match (a, b) {
+            (V::String(a), V::String(b)) => a.cmp(b),
+            (V::String(_), _) => Ordering::Greater,
+            (_, V::String(_)) => Ordering::Less,
+            (V::Int(a), V::Int(b)) => a.cmp(b),
+            (V::Int(a), V::Double(b)) => f64::from(*a).total_cmp(b),
+            (V::Double(a), V::Int(b)) => a.total_cmp(&f64::from(*b)),
+            (V::Double(a), V::Double(b)) => a.total_cmp(b),
+        };
+        artifacts.sort_by(|a, b| match (value(a, name), value(b, name)) {
+            (Some(x), Some(y)) => {
+                let ordering = if self.asc {
+                    compare(x, y)
+                } else {
+                    compare(y, x)
+                };
+                ordering.then_with(|| a.id.cmp(&b.id))
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => a.id.cmp(&b.id),
+        });
+    }
+
+    // Keeps the most recently created artifact per value of the `key` property (the order is preserved).
+    //
+    // Integral doubles are keyed as ints so that e.g. `1` and `1.0` are regarded as the same value.
+    fn dedup(
+        &self,
+        artifacts: Vec<mlmd::metadata::Artifact>,
+        key: &str,
+    ) -> anyhow::Result<Vec<mlmd::metadata::Artifact>> {
+        let mut newest_first = artifacts.iter().collect::<Vec<_>>();
+        newest_first.sort_by(|a, b| ArtifactOrderByField::CreateTime.compare(b, a));
+
+        let mut seen = BTreeSet::new();
+        let mut kept = BTreeSet::new();
+        for artifact in newest_first {
+            let value = artifact
+                .properties
+                .get(key)
+                .or_else(|| artifact.custom_properties.get(key))
+                .map(|x| match PropertyValue::from(x.clone()) {
+                    x @ PropertyValue::Double(_) => x.coerce(&PropertyType::Int).unwrap_or(x),
+                    x => x,
+                })
+                .map(|x| serde_json::to_string(&x))
+                .transpose()?;
+            if value.is_none() && matches!(self.dedup_missing, DedupMissing::Drop) {
+                continue;
+            }
+            if seen.insert(value) {
+                kept.insert(artifact.id);
+            }
+        }
         Ok(artifacts
             .into_iter()
-            .map(|x| Artifact::new(artifact_types[&x.type_id].clone(), x))
+            .filter(|x| kept.contains(&x.id))
             .collect())
     }
 
+    /// `$ mlmdquery get artifacts --group-into-contexts` implementation.
+    pub async fn get_grouped_by_contexts(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<BTreeMap<i32, Vec<Artifact>>> {
+        let artifacts = self.get(store).await?;
+        let indices = artifacts
+            .iter()
+            .enumerate()
+            .map(|(i, x)| (x.id, i))
+            .collect::<BTreeMap<_, _>>();
+
+        let artifact_ids = indices
+            .keys()
+            .copied()
+            .map(mlmd::metadata::ArtifactId::new)
+            .collect::<Vec<_>>();
+
+        let mut context_ids = BTreeSet::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let contexts = store
+                .get_contexts()
+                .artifacts(chunk.iter().copied())
+                .execute()
+                .await?;
+            context_ids.extend(contexts.into_iter().map(|x| x.id));
+        }
+
+        let mut groups = BTreeMap::new();
+        for context_id in context_ids {
+            // Only the members in the result are fetched (a context can have far more members than them).
+            let mut group = Vec::new();
+            for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+                let members = store
+                    .get_artifacts()
+                    .context(context_id)
+                    .ids(chunk.iter().copied())
+                    .execute()
+                    .await?;
+                group.extend(
+                    members
+                        .iter()
+                        .filter_map(|x| indices.get(&x.id.get()).copied()),
+                );
+            }
+            group.sort_unstable();
+            groups.insert(
+                context_id.get(),
+                group.into_iter().map(|i| artifacts[i].clone()).collect(),
+            );
+        }
+        Ok(groups)
+    }
+
     async fn get_artifact_types(
         &self,
         store: &mut mlmd::MetadataStore,
         artifacts: &[mlmd::metadata::Artifact],
-    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, String>> {
+    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, Type>> {
         Ok(store
             .get_artifact_types()
             .ids(
@@ -253,7 +1277,7 @@ impl GetArtifactsOpt {
             .execute()
             .await?
             .into_iter()
-            .map(|x| (x.id, x.name))
+            .map(|x| (x.id, Type::from(x)))
             .collect::<BTreeMap<_, _>>())
     }
 }
@@ -0,0 +1,372 @@
+//! On-disk cache of graph traversal results.
+use crate::graph::{Edge, Node, NodeId, Traversal};
+use crate::serialize::{ArtifactState, EventStep, EventType, ExecutionState};
+use anyhow::Context as _;
+use mlmd::metadata::{Artifact, ArtifactId, Event, Execution, ExecutionId, PropertyValue, TypeId};
+use sha2::{Digest as _, Sha256};
+use std::collections::BTreeMap;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Reads the traversal cached under `key` if it exists and is younger than `ttl`.
+///
+/// A cache file that cannot be parsed (e.g., one truncated by an interrupted write) is treated as a miss.
+pub(crate) fn load(
+    dir: &Path,
+    ttl: Duration,
+    key: &impl serde::Serialize,
+) -> anyhow::Result<Option<Traversal>> {
+    let path = cache_path(dir, key)?;
+    let modified = match std::fs::metadata(&path) {
+        Ok(x) => x.modified()?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    if modified.elapsed().map_or(true, |elapsed| elapsed > ttl) {
+        return Ok(None);
+    }
+
+    let file = std::fs::File::open(&path)?;
+    match serde_json::from_reader::<_, CachedTraversal>(std::io::BufReader::new(file)) {
+        Ok(cached) => Ok(Some(cached.into())),
+        Err(e) if e.is_io() => {
+            Err(e).with_context(|| format!("failed to read the cache file {}", path.display()))
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+/// Writes `traversal` to the cache under `key`.
+///
+/// The file is written to a temporary path first and then renamed, so readers never see a partial file.
+pub(crate) fn save(
+    dir: &Path,
+    key: &impl serde::Serialize,
+    traversal: &Traversal,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let path = cache_path(dir, key)?;
+    let tmp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    let file = std::fs::File::create(&tmp_path)
+        .with_context(|| format!("failed to create the cache file {}", tmp_path.display()))?;
+    let mut writer = std::io::BufWriter::new(file);
+    serde_json::to_writer(&mut writer, &CachedTraversal::from(traversal))?;
+    writer.flush()?;
+    drop(writer);
+    std::fs::rename(&tmp_path, &path)
+        .with_context(|| format!("failed to write the cache file {}", path.display()))?;
+    Ok(())
+}
+
+// Names the cache file after the SHA-256 digest of the JSON-serialized key,
+// which (unlike `DefaultHasher`) is stable across Rust versions and builds.
+fn cache_path(dir: &Path, key: &impl serde::Serialize) -> anyhow::Result<PathBuf> {
+    let digest = Sha256::digest(serde_json::to_vec(key)?);
+    Ok(dir.join(format!("{:x}.json", digest)))
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CachedTraversal {
+    nodes: Vec<CachedNode>,
+    edges: Vec<CachedEvent>,
+    boundaries: Vec<CachedNodeId>,
+    truncated: bool,
+}
+
+impl From<&Traversal> for CachedTraversal {
+    fn from(x: &Traversal) -> Self {
+        Self {
+            nodes: x.nodes.values().map(CachedNode::from).collect(),
+            edges: x
+                .edges
+                .iter()
+                .map(|x| CachedEvent::from(x.event()))
+                .collect(),
+            boundaries: x
+                .boundaries
+                .iter()
+                .copied()
+                .map(CachedNodeId::from)
+                .collect(),
+            truncated: x.truncated,
+        }
+    }
+}
+
+impl From<CachedTraversal> for Traversal {
+    fn from(x: CachedTraversal) -> Self {
+        Self {
+            nodes: x
+                .nodes
+                .into_iter()
+                .map(Node::from)
+                .map(|node| (node.id(), node))
+                .collect(),
+            edges: x.edges.into_iter().map(|x| Edge::new(x.into())).collect(),
+            boundaries: x.boundaries.into_iter().map(NodeId::from).collect(),
+            truncated: x.truncated,
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CachedNodeId {
+    Artifact(i32),
+    Execution(i32),
+}
+
+impl From<NodeId> for CachedNodeId {
+    fn from(x: NodeId) -> Self {
+        match x {
+            NodeId::Artifact(x) => Self::Artifact(x.get()),
+            NodeId::Execution(x) => Self::Execution(x.get()),
+        }
+    }
+}
+
+impl From<CachedNodeId> for NodeId {
+    fn from(x: CachedNodeId) -> Self {
+        match x {
+            CachedNodeId::Artifact(x) => Self::Artifact(ArtifactId::new(x)),
+            CachedNodeId::Execution(x) => Self::Execution(ExecutionId::new(x)),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CachedNode {
+    Artifact {
+        id: i32,
+        type_id: i32,
+        name: Option<String>,
+        uri: Option<String>,
+        state: ArtifactState,
+        ctime: Duration,
+        mtime: Duration,
+        properties: BTreeMap<String, CachedPropertyValue>,
+        custom_properties: BTreeMap<String, CachedPropertyValue>,
+    },
+    Execution {
+        id: i32,
+        type_id: i32,
+        name: Option<String>,
+        state: ExecutionState,
+        ctime: Duration,
+        mtime: Duration,
+        properties: BTreeMap<String, CachedPropertyValue>,
+        custom_properties: BTreeMap<String, CachedPropertyValue>,
+    },
+}
+
+impl From<&Node> for CachedNode {
+    fn from(x: &Node) -> Self {
+        match x {
+            Node::Artifact(x) => Self::Artifact {
+                id: x.id.get(),
+                type_id: x.type_id.get(),
+                name: x.name.clone(),
+                uri: x.uri.clone(),
+                state: x.state.into(),
+                ctime: x.create_time_since_epoch,
+                mtime: x.last_update_time_since_epoch,
+                properties: x
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone().into()))
+                    .collect(),
+                custom_properties: x
+                    .custom_properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone().into()))
+                    .collect(),
+            },
+            Node::Execution(x) => Self::Execution {
+                id: x.id.get(),
+                type_id: x.type_id.get(),
+                name: x.name.clone(),
+                state: x.last_known_state.into(),
+                ctime: x.create_time_since_epoch,
+                mtime: x.last_update_time_since_epoch,
+                properties: x
+                    .properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone().into()))
+                    .collect(),
+                custom_properties: x
+                    .custom_properties
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.clone().into()))
+                    .collect(),
+            },
+        }
+    }
+}
+
+impl From<CachedNode> for Node {
+    fn from(x: CachedNode) -> Self {
+        match x {
+            CachedNode::Artifact {
+                id,
+                type_id,
+                name,
+                uri,
+                state,
+                ctime,
+                mtime,
+                properties,
+                custom_properties,
+            } => Self::Artifact(Artifact {
+                id: ArtifactId::new(id),
+                type_id: TypeId::new(type_id),
+                name,
+                uri,
+                state: state.into(),
+                create_time_since_epoch: ctime,
+                last_update_time_since_epoch: mtime,
+                properties: properties.into_iter().map(|(k, v)| (k, v.into())).collect(),
+                custom_properties: custom_properties
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect(),
+            }),
+            CachedNode::Execution {
+                id,
+                type_id,
+                name,
+                state,
+                ctime,
+                mtime,
+                properties,
+                custom_properties,
+            } => Self::Execution(Execution {
+                id: ExecutionId::new(id),
+                type_id: TypeId::new(type_id),
+                name,
+                last_known_state: state.into(),
+                create_time_since_epoch: ctime,
+                last_update_time_since_epoch: mtime,
+                properties: properties.into_iter().map(|(k, v)| (k, v.into())).collect(),
+                custom_properties: custom_properties
+                    .into_iter()
+                    .map(|(k, v)| (k, v.into()))
+                    .collect(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct CachedEvent {
+    artifact: i32,
+    execution: i32,
+    #[serde(rename = "type")]
+    event_type: EventType,
+    path: Vec<EventStep>,
+    time: Duration,
+}
+
+impl From<&Event> for CachedEvent {
+    fn from(x: &Event) -> Self {
+        Self {
+            artifact: x.artifact_id.get(),
+            execution: x.execution_id.get(),
+            event_type: x.ty.into(),
+            path: x.path.iter().cloned().map(EventStep::from).collect(),
+            time: x.create_time_since_epoch,
+        }
+    }
+}
+
+impl From<CachedEvent> for Event {
+    fn from(x: CachedEvent) -> Self {
+        Self {
+            artifact_id: ArtifactId::new(x.artifact),
+            execution_id: ExecutionId::new(x.execution),
+            ty: x.event_type.into(),
+            path: x.path.into_iter().map(From::from).collect(),
+            create_time_since_epoch: x.time,
+        }
+    }
+}
+
+// Unlike `crate::serialize::PropertyValue`, this tags each value with its type and writes non-finite doubles as strings
+// ("NaN", "inf" and "-inf") so that every value survives a round-trip through JSON.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum CachedPropertyValue {
+    Int(i32),
+    Double(#[serde(with = "non_finite_double")] f64),
+    String(String),
+}
+
+impl From<PropertyValue> for CachedPropertyValue {
+    fn from(x: PropertyValue) -> Self {
+        match x {
+            PropertyValue::Int(x) => Self::Int(x),
+            PropertyValue::Double(x) => Self::Double(x),
+            PropertyValue::String(x) => Self::String(x),
+        }
+    }
+}
+
+impl From<CachedPropertyValue> for PropertyValue {
+    fn from(x: CachedPropertyValue) -> Self {
+        match x {
+            CachedPropertyValue::Int(x) => Self::Int(x),
+            CachedPropertyValue::Double(x) => Self::Double(x),
+            CachedPropertyValue::String(x) => Self::String(x),
+        }
+    }
+}
+
+mod non_finite_double {
+    use serde::{Deserialize as _, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(x: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if x.is_finite() {
+            serializer.serialize_f64(*x)
+        } else {
+            serializer.serialize_str(&x.to_string())
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Double {
+            Finite(f64),
+            NonFinite(String),
+        }
+
+        match Double::deserialize(deserializer)? {
+            Double::Finite(x) => Ok(x),
+            Double::NonFinite(x) => x.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn non_finite_doubles_round_trip() {
+        for x in [1.5, f64::INFINITY, f64::NEG_INFINITY] {
+            let json = serde_json::to_string(&CachedPropertyValue::Double(x)).unwrap();
+            let value: CachedPropertyValue = serde_json::from_str(&json).unwrap();
+            assert!(
+                matches!(value, CachedPropertyValue::Double(y) if y == x),
+                "{}",
+                json
+            );
+        }
+
+        let json = serde_json::to_string(&CachedPropertyValue::Double(f64::NAN)).unwrap();
+        assert_eq!(json, r#"{"double":"NaN"}"#);
+        let value: CachedPropertyValue = serde_json::from_str(&json).unwrap();
+        assert!(matches!(value, CachedPropertyValue::Double(y) if y.is_nan()));
+    }
+}
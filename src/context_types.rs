@@ -1,14 +1,20 @@
 //! `$ mlmdquery {get,count} context-types` implementation.
+use crate::db::{fetch_all_pages, DbOpt};
 use crate::serialize::Type;
 
 /// `$ mlmdquery {get,count} context-types` options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 pub struct ContextTypesOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    #[serde(skip)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub db: DbOpt,
+
+    /// If specified, the number of contexts that set each declared property will be reported as `usage`.
+    #[structopt(long)]
+    #[serde(default)]
+    pub usage: bool,
 }
 
 impl ContextTypesOpt {
@@ -20,6 +26,21 @@ impl ContextTypesOpt {
     /// `$ mlmdquery get context-types` implementation.
     pub async fn get(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Vec<Type>> {
         let types = store.get_context_types().execute().await?;
-        Ok(types.into_iter().map(Type::from).collect())
+        let mut types = types.into_iter().map(Type::from).collect::<Vec<_>>();
+        if self.usage {
+            for ty in &mut types {
+                let contexts = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+                    let request = store
+                        .get_contexts()
+                        .ty(&ty.name)
+                        .limit(limit)
+                        .offset(offset);
+                    Box::pin(async move { Ok(request.execute().await?) })
+                })
+                .await?;
+                ty.set_usage(contexts.iter().flat_map(|x| x.properties.keys()));
+            }
+        }
+        Ok(types)
     }
 }
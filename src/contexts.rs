@@ -1,5 +1,14 @@
 //! `$ mlmdquery {get,count} contexts` implementation.
-use crate::serialize::Context;
+use crate::db::{fetch_all_pages, fetch_page, DbOpt, ID_CHUNK_SIZE};
+use crate::error::NotFoundError;
+use crate::graph::TimeZone;
+use crate::interrupt::is_interrupted;
+use crate::query::{
+    count_needs_fetch, dedup_ids, explain_filters, paginate, sort_by_id_order, FilterExplanation,
+    FilterMode, NonFinitePolicy, Page, PropertyFilter, TimePoint, TimeUnit,
+};
+use crate::serialize::{coerce_properties, handle_nonfinite, Context, Type};
+use anyhow::Context as _;
 use std::collections::{BTreeMap, BTreeSet};
 use std::time::Duration;
 
@@ -8,10 +17,10 @@ use std::time::Duration;
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "kebab-case")]
 pub struct CommonContextsOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    #[serde(skip)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub db: DbOpt,
 
     /// Target context IDs.
     #[structopt(long = "id")]
@@ -44,36 +53,134 @@ pub struct CommonContextsOpt {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub executions: Vec<i32>,
 
-    /// Start of creation time (UNIX timestamp seconds).
+    /// Start of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_start: Option<f64>,
+    pub ctime_start: Option<TimePoint>,
 
-    /// End of creation time (UNIX timestamp seconds).
+    /// End of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_end: Option<f64>,
+    pub ctime_end: Option<TimePoint>,
 
-    /// Start of update time (UNIX timestamp seconds).
+    /// Start of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_start: Option<f64>,
+    pub mtime_start: Option<TimePoint>,
 
-    /// End of update time (UNIX timestamp seconds).
+    /// End of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_end: Option<f64>,
+    pub mtime_end: Option<TimePoint>,
+
+    /// Time zone in which the date strings given to `--{c,m}time-{start,end}` are interpreted
+    /// (unless they contain an offset).
+    #[structopt(long, default_value = "utc", possible_values = TimeZone::POSSIBLE_VALUES, case_insensitive = true)]
+    #[serde(default = "TimeZone::utc")]
+    pub input_timezone: TimeZone,
+
+    /// If specified, only contexts without any artifacts and executions are targeted.
+    ///
+    /// Note that this filter is applied after fetching contexts from the DB
+    /// (the members of each context are counted one context at a time).
+    #[structopt(long)]
+    #[serde(default)]
+    pub empty: bool,
+
+    /// Target context custom property values (`NAME=VALUE`, can be specified multiple times).
+    ///
+    /// Only the contexts having all the given values in `custom_properties` are returned.
+    /// VALUE is parsed as an int, a double or a string (in that order), and VALUE prefixed with `@`
+    /// is read from the file at the following path.
+    ///
+    /// Note that this filter is applied after fetching contexts from the DB.
+    #[structopt(long = "custom-property")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_properties: Vec<PropertyFilter>,
 }
 
 impl CommonContextsOpt {
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        use FilterMode::*;
+
+        explain_filters(&[
+            ("--id", !self.ids.is_empty(), ServerSide),
+            ("--name", self.name.is_some(), ServerSide),
+            ("--name-pattern", self.name_pattern.is_some(), ServerSide),
+            ("--type", self.type_name.is_some(), ServerSide),
+            ("--artifact", !self.artifacts.is_empty(), ServerSide),
+            ("--execution", !self.executions.is_empty(), ServerSide),
+            ("--ctime-start", self.ctime_start.is_some(), ServerSide),
+            ("--ctime-end", self.ctime_end.is_some(), ServerSide),
+            ("--mtime-start", self.mtime_start.is_some(), ServerSide),
+            ("--mtime-end", self.mtime_end.is_some(), ServerSide),
+            ("--empty", self.empty, ClientSide),
+            (
+                "--custom-property",
+                !self.custom_properties.is_empty(),
+                ClientSide,
+            ),
+        ])
+    }
+
+    fn has_client_side_filters(&self) -> bool {
+        self.explain()
+            .iter()
+            .any(|x| x.mode != FilterMode::ServerSide)
+    }
+
+    async fn execute(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        order_by: ContextOrderByField,
+        asc: bool,
+    ) -> anyhow::Result<Vec<mlmd::metadata::Context>> {
+        let contexts = self
+            .request(store)?
+            .order_by(order_by.into(), asc)
+            .execute()
+            .await?;
+        self.filter(store, contexts).await
+    }
+
+    // Applies the client-side filters (`--custom-property` and `--empty`).
+    async fn filter(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        mut contexts: Vec<mlmd::metadata::Context>,
+    ) -> anyhow::Result<Vec<mlmd::metadata::Context>> {
+        contexts.retain(|context| {
+            self.custom_properties
+                .iter()
+                .all(|x| x.matches(&context.custom_properties))
+        });
+        if !self.empty {
+            return Ok(contexts);
+        }
+
+        let mut filtered = Vec::new();
+        for context in contexts {
+            if store.get_artifacts().context(context.id).count().await? == 0
+                && store.get_executions().context(context.id).count().await? == 0
+            {
+                filtered.push(context);
+            }
+        }
+        Ok(filtered)
+    }
+
     fn request<'a>(
         &self,
         store: &'a mut mlmd::MetadataStore,
-    ) -> mlmd::requests::GetContextsRequest<'a> {
+    ) -> anyhow::Result<mlmd::requests::GetContextsRequest<'a>> {
         let mut request = store.get_contexts();
 
         if !self.ids.is_empty() {
-            request = request.ids(self.ids.iter().copied().map(mlmd::metadata::ContextId::new));
+            request = request.ids(
+                dedup_ids(&self.ids)
+                    .into_iter()
+                    .map(mlmd::metadata::ContextId::new),
+            );
         }
         match (&self.name, &self.name_pattern, &self.type_name) {
             (Some(name), None, Some(type_name)) => {
@@ -99,24 +206,25 @@ impl CommonContextsOpt {
                 .copied()
                 .map(mlmd::metadata::ExecutionId::new),
         );
-        request = match (self.ctime_start, self.ctime_end) {
+        let time = |x: &Option<TimePoint>| {
+            x.as_ref()
+                .map(|x| x.to_duration(self.input_timezone))
+                .transpose()
+        };
+        request = match (time(&self.ctime_start)?, time(&self.ctime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.create_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.create_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.create_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.create_time(s..),
+            (None, Some(e)) => request.create_time(..e),
+            (Some(s), Some(e)) => request.create_time(s..e),
         };
-        request = match (self.mtime_start, self.mtime_end) {
+        request = match (time(&self.mtime_start)?, time(&self.mtime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.update_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.update_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.update_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.update_time(s..),
+            (None, Some(e)) => request.update_time(..e),
+            (Some(s), Some(e)) => request.update_time(s..e),
         };
 
-        request
+        Ok(request)
     }
 }
 
@@ -135,6 +243,15 @@ pub enum ContextOrderByField {
 
 impl ContextOrderByField {
     const POSSIBLE_VALUES: &'static [&'static str] = &["id", "name", "ctime", "mtime"];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::CreateTime => "ctime",
+            Self::UpdateTime => "mtime",
+        }
+    }
 }
 
 impl Default for ContextOrderByField {
@@ -180,7 +297,14 @@ pub struct CountContextsOpt {
 impl CountContextsOpt {
     /// `$ mlmdquery count contexts` implementation.
     pub async fn count(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<usize> {
-        let n = self.common.request(store).count().await?;
+        if count_needs_fetch(&self.common.explain()) {
+            let contexts = self
+                .common
+                .execute(store, ContextOrderByField::Id, true)
+                .await?;
+            return Ok(contexts.len());
+        }
+        let n = self.common.request(store)?.count().await?;
         Ok(n)
     }
 }
@@ -199,13 +323,39 @@ pub struct GetContextsOpt {
     #[serde(default)]
     pub order_by: ContextOrderByField,
 
+    /// If specified, the search results will be sorted by the latest event time among the
+    /// artifacts and executions of each context (this overrides `--order-by`).
+    ///
+    /// Note that this requires fetching all matching contexts and their events from the DB.
+    #[structopt(long)]
+    #[serde(default)]
+    pub activity: bool,
+
+    /// If specified, `latest_event_time` and `latest_event_type` (of the most recent event among
+    /// the artifacts and executions of the context) are added to each context.
+    #[structopt(long)]
+    #[serde(default)]
+    pub with_latest_event: bool,
+
+    /// If specified, the search results are sorted in the order of the `--id` arguments (overriding `--order-by`).
+    ///
+    /// Note that all the matching contexts are fetched from the DB before `--limit` and `--offset` are applied.
+    #[structopt(long, requires("ids"), conflicts_with("activity"))]
+    #[serde(default)]
+    pub preserve_order: bool,
+
     /// If specified, the search results will be sorted in ascending order.
     #[structopt(long)]
     #[serde(default)]
     pub asc: bool,
 
-    /// Maximum number of artifacts in a search result.
-    #[structopt(long, default_value = "100")]
+    /// If specified, the search results will be sorted in descending order (default).
+    #[structopt(long, conflicts_with("asc"))]
+    #[serde(default)]
+    pub desc: bool,
+
+    /// Maximum number of artifacts in a search result (0 means unlimited).
+    #[structopt(long, env = "MLMD_LIMIT_CONTEXTS", default_value = "100")]
     #[serde(default = "GetContextsOpt::limit_default")]
     pub limit: usize,
 
@@ -213,43 +363,294 @@ pub struct GetContextsOpt {
     #[structopt(long, default_value = "0")]
     #[serde(default)]
     pub offset: usize,
+
+    /// If specified, `properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_properties: bool,
+
+    /// If specified, `custom_properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_custom_properties: bool,
+
+    /// If specified, property values are converted to the types declared in their type schema.
+    #[structopt(long)]
+    #[serde(default)]
+    pub coerce_to_schema: bool,
+
+    /// If specified, property values that cannot be converted to their declared types cause an error.
+    #[structopt(long, requires("coerce-to-schema"))]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Unit of the timestamps (`ctime`, `mtime` and `latest_event_time`) in the output.
+    #[structopt(long, default_value = "s", possible_values = TimeUnit::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub time_unit: TimeUnit,
+
+    /// How to output non-finite (`NaN` or infinite) double property values.
+    ///
+    /// `null` outputs `null`, `string` outputs "NaN", "Infinity" or "-Infinity", and `error` fails the query.
+    #[structopt(long, default_value = "null", possible_values = NonFinitePolicy::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub nonfinite: NonFinitePolicy,
 }
 
 impl GetContextsOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery get contexts --type Experiment\n",
+        "    $ mlmdquery get contexts --artifact 1 --execution 1\n",
+        "    $ mlmdquery get contexts --activity --limit 10\n",
+        "    $ mlmdquery get contexts --type PipelineRun --with-latest-event\n",
+        "    $ mlmdquery get contexts --id 3 --id 1 --id 2 --preserve-order\n",
+    );
+
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        let mut filters = self.common.explain();
+        filters.extend(explain_filters(&[(
+            "--preserve-order",
+            self.preserve_order,
+            FilterMode::ClientSide,
+        )]));
+        filters
+    }
+
     fn limit_default() -> usize {
-        100
+        std::env::var("MLMD_LIMIT_CONTEXTS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100)
     }
 
     /// `$ mlmdquery get context` implementation.
     pub async fn get(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Vec<Context>> {
-        let contexts = self
-            .common
-            .request(store)
-            .limit(self.limit)
-            .offset(self.offset)
-            .order_by(self.order_by.into(), self.asc)
-            .execute()
-            .await?;
+        Ok(self.get_page(store).await?.items)
+    }
+
+    /// Same as [`GetContextsOpt::get`], but also tells whether more contexts than `--limit` existed.
+    pub async fn get_page(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Page<Context>> {
+        let Page {
+            items: contexts,
+            truncated,
+        } = if self.activity {
+            self.get_by_activity(store).await?
+        } else if self.common.has_client_side_filters() || self.preserve_order {
+            let mut contexts = self.common.execute(store, self.order_by, self.asc).await?;
+            if self.preserve_order {
+                sort_by_id_order(&mut contexts, &self.common.ids, |x| x.id.get());
+            }
+            paginate(contexts, self.offset, self.limit)
+        } else {
+            fetch_page(store, self.limit, self.offset, |store, limit, offset| {
+                let request = self.common.request(store).map(|x| {
+                    x.limit(limit)
+                        .offset(offset)
+                        .order_by(self.order_by.into(), self.asc)
+                });
+                Box::pin(async move { Ok(request?.execute().await?) })
+            })
+            .await?
+        };
 
         let context_types = self.get_context_types(store, &contexts).await?;
-        Ok(contexts
+        let mut contexts = contexts
             .into_iter()
-            .map(|x| Context {
-                id: x.id.get(),
-                name: x.name,
-                type_name: context_types[&x.type_id].clone(),
-                ctime: x.create_time_since_epoch.as_secs_f64(),
-                mtime: x.last_update_time_since_epoch.as_secs_f64(),
-                properties: x
-                    .properties
-                    .into_iter()
-                    .map(|(k, v)| (k, v.into()))
-                    .collect(),
-                custom_properties: x
-                    .custom_properties
-                    .into_iter()
-                    .map(|(k, v)| (k, v.into()))
-                    .collect(),
+            .map(|mut x| {
+                if self.no_properties {
+                    x.properties.clear();
+                }
+                if self.no_custom_properties {
+                    x.custom_properties.clear();
+                }
+                let ty = &context_types[&x.type_id];
+                let mut context = Context {
+                    id: x.id.get(),
+                    name: x.name,
+                    type_name: ty.name.clone(),
+                    ctime: self.time_unit.timestamp(x.create_time_since_epoch),
+                    mtime: self.time_unit.timestamp(x.last_update_time_since_epoch),
+                    properties: x
+                        .properties
+                        .into_iter()
+                        .map(|(k, v)| (k, v.into()))
+                        .collect(),
+                    custom_properties: x
+                        .custom_properties
+                        .into_iter()
+                        .map(|(k, v)| (k, v.into()))
+                        .collect(),
+                    latest_event_time: None,
+                    latest_event_type: None,
+                };
+                if self.coerce_to_schema {
+                    coerce_properties(&mut context.properties, &ty.properties, self.strict)
+                        .with_context(|| format!("context {}", context.id))?;
+                }
+                handle_nonfinite(&mut context.properties, self.nonfinite)
+                    .and_then(|()| handle_nonfinite(&mut context.custom_properties, self.nonfinite))
+                    .with_context(|| format!("context {}", context.id))?;
+                Ok(context)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if self.with_latest_event {
+            let context_ids = contexts
+                .iter()
+                .map(|x| mlmd::metadata::ContextId::new(x.id))
+                .collect::<Vec<_>>();
+            let mut latest_events = self.get_latest_events(store, &context_ids).await?;
+            for context in &mut contexts {
+                let latest = latest_events
+                    .remove(&mlmd::metadata::ContextId::new(context.id))
+                    .flatten();
+                context.latest_event_time =
+                    Some(latest.as_ref().map(|x| self.time_unit.timestamp(x.0)));
+                context.latest_event_type = Some(latest.map(|x| x.1.into()));
+            }
+        }
+        Ok(Page {
+            items: contexts,
+            truncated,
+        })
+    }
+
+    async fn get_by_activity(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<Page<mlmd::metadata::Context>> {
+        let contexts = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+            let request = self
+                .common
+                .request(store)
+                .map(|x| x.limit(limit).offset(offset));
+            Box::pin(async move { Ok(request?.execute().await?) })
+        })
+        .await?;
+        let mut contexts = self.common.filter(store, contexts).await?;
+
+        let context_ids = contexts.iter().map(|x| x.id).collect::<Vec<_>>();
+        let activities = self
+            .get_latest_events(store, &context_ids)
+            .await?
+            .into_iter()
+            .map(|(id, latest)| (id, latest.map(|x| x.0)))
+            .collect::<BTreeMap<_, _>>();
+        contexts.sort_by(|a, b| {
+            let ordering = activities[&a.id]
+                .cmp(&activities[&b.id])
+                .then_with(|| a.id.cmp(&b.id));
+            if self.asc {
+                ordering
+            } else {
+                ordering.reverse()
+            }
+        });
+        Ok(paginate(contexts, self.offset, self.limit))
+    }
+
+    // Returns the latest event relating to the members (artifacts and executions) of each context.
+    //
+    // The events of the members of all the contexts are fetched at once (in chunks of `ID_CHUNK_SIZE` IDs)
+    // instead of one context at a time, and the members of each context are fetched page by page.
+    async fn get_latest_events(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        context_ids: &[mlmd::metadata::ContextId],
+    ) -> anyhow::Result<
+        BTreeMap<mlmd::metadata::ContextId, Option<(Duration, mlmd::metadata::EventType)>>,
+    > {
+        let mut members = Vec::new();
+        for &context_id in context_ids {
+            if is_interrupted() {
+                // The remaining contexts are reported as having no latest event.
+                members.push((context_id, Vec::new(), Vec::new()));
+                continue;
+            }
+            let artifact_ids = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+                let request = store
+                    .get_artifacts()
+                    .context(context_id)
+                    .limit(limit)
+                    .offset(offset);
+                Box::pin(
+                    async move { Ok(request.execute().await?.into_iter().map(|x| x.id).collect()) },
+                )
+            })
+            .await?;
+            let execution_ids = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+                let request = store
+                    .get_executions()
+                    .context(context_id)
+                    .limit(limit)
+                    .offset(offset);
+                Box::pin(
+                    async move { Ok(request.execute().await?.into_iter().map(|x| x.id).collect()) },
+                )
+            })
+            .await?;
+            members.push((context_id, artifact_ids, execution_ids));
+        }
+
+        let artifact_ids = members
+            .iter()
+            .flat_map(|x| x.1.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let execution_ids = members
+            .iter()
+            .flat_map(|x| x.2.iter().copied())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let latest = |x: &mut Option<(Duration, mlmd::metadata::EventType)>,
+                      event: &mlmd::metadata::Event| {
+            if !matches!(x, Some(x) if x.0 >= event.create_time_since_epoch) {
+                *x = Some((event.create_time_since_epoch, event.ty));
+            }
+        };
+        let mut artifact_events = BTreeMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let events = store
+                .get_events()
+                .artifacts(chunk.iter().copied())
+                .execute()
+                .await?;
+            for event in &events {
+                latest(artifact_events.entry(event.artifact_id).or_default(), event);
+            }
+        }
+        let mut execution_events = BTreeMap::new();
+        for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+            let events = store
+                .get_events()
+                .executions(chunk.iter().copied())
+                .execute()
+                .await?;
+            for event in &events {
+                latest(
+                    execution_events.entry(event.execution_id).or_default(),
+                    event,
+                );
+            }
+        }
+
+        Ok(members
+            .into_iter()
+            .map(|(context_id, artifact_ids, execution_ids)| {
+                let latest = artifact_ids
+                    .iter()
+                    .filter_map(|id| artifact_events.get(id).copied().flatten())
+                    .chain(
+                        execution_ids
+                            .iter()
+                            .filter_map(|id| execution_events.get(id).copied().flatten()),
+                    )
+                    .max_by_key(|x| x.0);
+                (context_id, latest)
             })
             .collect())
     }
@@ -258,7 +659,7 @@ impl GetContextsOpt {
         &self,
         store: &mut mlmd::MetadataStore,
         contexts: &[mlmd::metadata::Context],
-    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, String>> {
+    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, Type>> {
         Ok(store
             .get_context_types()
             .ids(
@@ -271,7 +672,49 @@ impl GetContextsOpt {
             .execute()
             .await?
             .into_iter()
-            .map(|x| (x.id, x.name))
+            .map(|x| (x.id, Type::from(x)))
             .collect::<BTreeMap<_, _>>())
     }
 }
+
+// Resolves the ID of the context having the given name (and type if specified).
+//
+// Fails if no context or multiple contexts match.
+pub(crate) async fn resolve_context_name(
+    store: &mut mlmd::MetadataStore,
+    name: &str,
+    type_name: Option<&str>,
+) -> anyhow::Result<mlmd::metadata::ContextId> {
+    let contexts = if let Some(type_name) = type_name {
+        store
+            .get_contexts()
+            .type_and_name(type_name, name)
+            .execute()
+            .await?
+    } else {
+        // The DB cannot look up a context by name without its type.
+        let mut contexts = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+            let request = store.get_contexts().limit(limit).offset(offset);
+            Box::pin(async move { Ok(request.execute().await?) })
+        })
+        .await?;
+        contexts.retain(|x| x.name == name);
+        contexts
+    };
+    match contexts.len() {
+        0 => match type_name {
+            Some(type_name) => Err(NotFoundError::new(format!(
+                "No such context: {:?} (type: {:?})",
+                name, type_name
+            ))
+            .into()),
+            None => Err(NotFoundError::new(format!("No such context: {:?}", name)).into()),
+        },
+        1 => Ok(contexts[0].id),
+        n => anyhow::bail!(
+            "{} contexts are named {:?} (specify `--context-type` to disambiguate)",
+            n,
+            name
+        ),
+    }
+}
@@ -0,0 +1,195 @@
+//! Database connection options.
+use crate::interrupt::is_interrupted;
+use crate::query::{truncate_to_limit, Page};
+use anyhow::Context as _;
+use mlmd::MetadataStore;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::time::Duration;
+
+/// Number of items fetched per request when paging through an unlimited (`--limit 0`) search result.
+pub(crate) const PAGE_SIZE: usize = 1000;
+
+/// Maximum number of IDs passed to a single request (to avoid huge SQL `IN` clauses).
+pub(crate) const ID_CHUNK_SIZE: usize = 1000;
+
+/// Future of a page of a search result (see [`fetch_all_pages`]).
+pub(crate) type PageFuture<'a, T> =
+    Pin<Box<dyn Future<Output = anyhow::Result<Vec<T>>> + Send + 'a>>;
+
+/// Fetches a search result starting at `offset`.
+///
+/// `fetch(store, limit, offset)` requests a single page.
+/// If `limit` is `0`, pages of `PAGE_SIZE` items are fetched until the result is exhausted (or Ctrl-C is pressed),
+/// otherwise only a page of `limit` items is fetched.
+pub(crate) async fn fetch_all_pages<T, F>(
+    store: &mut MetadataStore,
+    limit: usize,
+    offset: usize,
+    mut fetch: F,
+) -> anyhow::Result<Vec<T>>
+where
+    F: for<'a> FnMut(&'a mut MetadataStore, usize, usize) -> PageFuture<'a, T>,
+{
+    let page_size = if limit == 0 { PAGE_SIZE } else { limit };
+    let mut items = Vec::new();
+    loop {
+        let page = fetch(store, page_size, offset + items.len()).await?;
+        let done = limit != 0 || page.len() < page_size;
+        items.extend(page);
+        if done || is_interrupted() {
+            break;
+        }
+    }
+    Ok(items)
+}
+
+/// Like [`fetch_all_pages`], but fetches one more item than a non-zero `limit` to tell whether the result was truncated.
+pub(crate) async fn fetch_page<T, F>(
+    store: &mut MetadataStore,
+    limit: usize,
+    offset: usize,
+    fetch: F,
+) -> anyhow::Result<Page<T>>
+where
+    F: for<'a> FnMut(&'a mut MetadataStore, usize, usize) -> PageFuture<'a, T>,
+{
+    let fetch_limit = if limit == 0 { 0 } else { limit + 1 };
+    let mut items = fetch_all_pages(store, fetch_limit, offset, fetch).await?;
+    let truncated = truncate_to_limit(&mut items, limit);
+    Ok(Page { items, truncated })
+}
+
+/// Database connection options.
+#[derive(Debug, Clone, Default, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub struct DbOpt {
+    /// Database URL [env: MLMD_DB].
+    ///
+    /// The URL is resolved in the following order: `--db`, `--db-file`, `MLMD_DB` and `MLMD_DB_FILE`.
+    /// `${VAR}` placeholders in the URL are replaced with the values of the environment variables
+    /// (e.g., `mysql://user:${DB_PASS}@${DB_HOST}/mlmd`).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db: Option<String>,
+
+    /// File containing the database URL [env: MLMD_DB_FILE].
+    ///
+    /// This keeps credentials out of process listings and shell history.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_file: Option<PathBuf>,
+
+    /// Timeout of connecting to the database (seconds).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<f64>,
+
+    /// Glob pattern of SQLite database files to be queried as shards (e.g., `shards/mlmd-*.db`).
+    ///
+    /// The command is executed against each matching file and the results are concatenated
+    /// (counts are summed up and types are merged by name). Note that `--limit` and `--offset` are applied per shard.
+    /// The IDs in the result are those in each shard, so items from different shards may have the same ID
+    /// and IDs cannot be compared across shards.
+    /// This is only supported by standalone `get` and `count` commands.
+    #[structopt(long, conflicts_with_all(&["db", "db-file"]))]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub db_glob: Option<String>,
+}
+
+impl DbOpt {
+    /// Returns `true` if the database is explicitly specified by these options (i.e., not by environment variables).
+    pub fn is_specified(&self) -> bool {
+        self.db.is_some() || self.db_file.is_some() || self.db_glob.is_some()
+    }
+
+    /// Returns the database URL (in which `${VAR}` placeholders are expanded).
+    pub fn url(&self) -> anyhow::Result<String> {
+        expand_env_vars(&self.raw_url()?)
+    }
+
+    fn raw_url(&self) -> anyhow::Result<String> {
+        anyhow::ensure!(
+            self.db_glob.is_none(),
+            "`--db-glob` is only supported by standalone `get` and `count` commands"
+        );
+        if let Some(x) = &self.db {
+            return Ok(x.clone());
+        }
+        if let Some(x) = &self.db_file {
+            return read_url(x);
+        }
+        if let Ok(x) = std::env::var("MLMD_DB") {
+            return Ok(x);
+        }
+        if let Ok(x) = std::env::var("MLMD_DB_FILE") {
+            return read_url(Path::new(&x));
+        }
+        anyhow::bail!("no database is specified (please use `--db`, `--db-file`, `MLMD_DB` or `MLMD_DB_FILE`)")
+    }
+
+    /// Returns the SQLite URLs of the files matching `--db-glob` (in lexicographic order of their paths).
+    pub fn shard_urls(&self) -> anyhow::Result<Vec<String>> {
+        let pattern = self
+            .db_glob
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("`--db-glob` is not specified"))?;
+        let mut paths = glob::glob(pattern)
+            .with_context(|| format!("invalid glob pattern: {:?}", pattern))?
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+        anyhow::ensure!(!paths.is_empty(), "no file matches {:?}", pattern);
+        Ok(paths
+            .into_iter()
+            .map(|path| format!("sqlite://{}", path.display()))
+            .collect())
+    }
+
+    /// Connects to the database.
+    pub async fn connect(&self) -> anyhow::Result<MetadataStore> {
+        self.connect_to(&self.url()?).await
+    }
+
+    /// Connects to the database at `url` (`--connect-timeout` is applied).
+    pub async fn connect_to(&self, url: &str) -> anyhow::Result<MetadataStore> {
+        let connect = MetadataStore::connect(url);
+        let store = if let Some(timeout) = self.connect_timeout {
+            tokio::time::timeout(Duration::from_secs_f64(timeout), connect)
+                .await
+                .with_context(|| format!("could not connect within {}s", timeout))??
+        } else {
+            connect.await?
+        };
+        Ok(store)
+    }
+}
+
+fn expand_env_vars(url: &str) -> anyhow::Result<String> {
+    let mut expanded = String::new();
+    let mut rest = url;
+    while let Some(start) = rest.find("${") {
+        expanded.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| anyhow::anyhow!("unclosed `${{` in the database URL"))?;
+        let name = &rest[start + 2..start + end];
+        let value = std::env::var(name).with_context(|| {
+            format!(
+                "the environment variable {:?} referred to by the database URL is not set",
+                name
+            )
+        })?;
+        expanded.push_str(&value);
+        rest = &rest[start + end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+fn read_url(path: &Path) -> anyhow::Result<String> {
+    let url = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(url.trim().to_owned())
+}
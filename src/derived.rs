@@ -1,77 +1,98 @@
 //! `$ mlmdquery graph derived` implementation.
-use crate::graph::{Edge, Graph, Node, NodeId};
+use crate::db::DbOpt;
+use crate::graph::{resolve_origin_ids, CommonGraphOpt, Edge, Graph, NodeId, OriginId};
+use crate::interrupt::is_interrupted;
 use mlmd::metadata::{ArtifactId, EventType};
 use mlmd::MetadataStore;
-use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
 /// `$ mlmdquery graph derived` options.
 #[derive(Debug, structopt::StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct GraphDerivedOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
 
-    /// Target artifact ID.
-    pub artifact: i32,
-
-    /// Template to generate node URLs.
+    /// Target artifact IDs.
     ///
-    /// You can use the following variables in the template:
-    /// - `{node_type}`: "artifact" or "execution":
-    /// - `{id}`: Artifact or Execution ID (depending on `node_type`)
+    /// If `-` is specified or no ID is given, IDs separated by whitespace are read from stdin.
+    /// All the IDs are drawn in a single graph.
+    pub artifacts: Vec<OriginId>,
+
+    /// Maximum number of nodes in a graph.
     ///
-    /// Please refer to the [tinytemplate](https://docs.rs/tinytemplate/) doc for the features of the template engine.
-    #[structopt(long)]
-    pub url_template: Option<String>,
+    /// If the traversal reaches this limit, the graph is truncated and a warning is written to stderr.
+    #[structopt(long, default_value = "1000")]
+    pub max_nodes: usize,
+
+    /// Common options.
+    #[structopt(flatten)]
+    pub common: CommonGraphOpt,
 }
 
 impl GraphDerivedOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery graph derived 1 | dot -Tsvg > derived.svg\n",
+        "    $ mlmdquery graph derived 1 2 --max-nodes 100\n",
+        "    $ mlmdquery get artifacts --type Model --output-template '{id}' | mlmdquery graph derived -\n",
+    );
+
     /// `$ mlmdquery graph derived` implementation.
     pub async fn graph<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
-        let mut store = MetadataStore::connect(&self.db).await?;
+        let artifacts = resolve_origin_ids(&self.artifacts)?;
+        let mut store = self.db.connect().await?;
 
-        let origin = NodeId::Artifact(ArtifactId::new(self.artifact));
-        let mut stack = vec![origin];
-        let mut nodes = HashMap::new();
-        let mut edges = HashSet::new();
-        while let Some(id) = stack.pop() {
-            if nodes.contains_key(&id) {
-                continue;
+        let origins = artifacts
+            .iter()
+            .map(|&id| NodeId::Artifact(ArtifactId::new(id)))
+            .collect::<Vec<_>>();
+        let cache_key = (self.db.url()?, "derived", &artifacts, self.max_nodes);
+        let traversal = if let Some(x) = self.common.load_cache(&cache_key)? {
+            x
+        } else {
+            let x = self
+                .common
+                .traverse(
+                    &mut store,
+                    origins.clone(),
+                    Some(self.max_nodes),
+                    None,
+                    |store, id| Box::pin(get_edges(store, id)),
+                )
+                .await?;
+            if !is_interrupted() {
+                self.common.save_cache(&cache_key, &x)?;
             }
+            x
+        };
 
-            let node = get_node(&mut store, id).await?;
-            nodes.insert(id, node);
-
-            for edge in get_edges(&mut store, id).await? {
-                stack.push(edge.from_node());
-                stack.push(edge.to_node());
-                edges.insert(edge);
+        let mut graph = Graph::new(
+            &mut store,
+            origins,
+            traversal.nodes,
+            traversal.edges,
+            self.common.clone(),
+        )
+        .await?;
+        if traversal.truncated {
+            if is_interrupted() {
+                eprintln!("warning: the graph was truncated because the traversal was interrupted");
+            } else {
+                eprintln!(
+                    "warning: the graph was truncated because it reached the node limit ({})",
+                    self.max_nodes
+                );
             }
+            graph.prune_dangling_edges();
         }
-
-        let graph = Graph::new(&mut store, origin, nodes, edges, self.url_template.clone()).await?;
         graph.generate(writer)?;
         Ok(())
     }
 }
 
-async fn get_node(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Node> {
-    match id {
-        NodeId::Artifact(id) => {
-            let mut artifacts = store.get_artifacts().id(id).execute().await?;
-            anyhow::ensure!(artifacts.len() == 1, "No such artifact: {}", id.get());
-            Ok(Node::Artifact(artifacts.remove(0)))
-        }
-        NodeId::Execution(id) => {
-            let mut executions = store.get_executions().id(id).execute().await?;
-            anyhow::ensure!(executions.len() == 1, "No such execution: {}", id.get());
-            Ok(Node::Execution(executions.remove(0)))
-        }
-    }
-}
-
 async fn get_edges(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Vec<Edge>> {
     match id {
         NodeId::Artifact(id) => {
@@ -0,0 +1,207 @@
+//! `$ mlmdquery diff *` implementation.
+use crate::db::DbOpt;
+use crate::error::NotFoundError;
+use crate::query::TimeUnit;
+use crate::serialize::{
+    Artifact, ArtifactPair, ArtifactsDiff, PropertiesDiff, PropertyMapDiff, PropertyValue,
+};
+use mlmd::metadata::{ArtifactId, ExecutionId, PropertyValues};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// `$ mlmdquery diff artifacts` options.
+#[derive(Debug, Clone, structopt::StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DiffArtifactsOpt {
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
+
+    /// Context ID of the first artifact set.
+    #[structopt(long)]
+    pub context_a: i32,
+
+    /// Context ID of the second artifact set.
+    #[structopt(long)]
+    pub context_b: i32,
+
+    /// Property (or custom property) used to match artifacts.
+    ///
+    /// If omitted, artifacts are matched by their type and name.
+    #[structopt(long)]
+    pub match_property: Option<String>,
+}
+
+impl DiffArtifactsOpt {
+    /// `$ mlmdquery diff artifacts` implementation.
+    pub async fn diff(&self) -> anyhow::Result<ArtifactsDiff> {
+        let mut store = self.db.connect().await?;
+        let a = get_context_artifacts(&mut store, self.context_a).await?;
+        let b = get_context_artifacts(&mut store, self.context_b).await?;
+
+        let mut diff = ArtifactsDiff::default();
+        let a = self.group_by_key(a, &mut diff.only_a)?;
+        let mut b = self.group_by_key(b, &mut diff.only_b)?;
+        for (key, a) in a {
+            let b = b.remove(&key).unwrap_or_default();
+            let n = std::cmp::min(a.len(), b.len());
+            let mut a = a.into_iter();
+            let mut b = b.into_iter();
+            for (a, b) in (&mut a).zip(&mut b).take(n) {
+                diff.both.push(ArtifactPair { a, b });
+            }
+            diff.only_a.extend(a);
+            diff.only_b.extend(b);
+        }
+        diff.only_b.extend(b.into_values().flatten());
+        Ok(diff)
+    }
+
+    fn group_by_key(
+        &self,
+        artifacts: Vec<Artifact>,
+        unmatched: &mut Vec<Artifact>,
+    ) -> anyhow::Result<BTreeMap<String, Vec<Artifact>>> {
+        let mut groups = BTreeMap::<_, Vec<_>>::new();
+        for artifact in artifacts {
+            if let Some(key) = self.key(&artifact)? {
+                groups.entry(key).or_default().push(artifact);
+            } else {
+                unmatched.push(artifact);
+            }
+        }
+        Ok(groups)
+    }
+
+    fn key(&self, artifact: &Artifact) -> anyhow::Result<Option<String>> {
+        if let Some(name) = &self.match_property {
+            let value = artifact
+                .properties
+                .get(name)
+                .or_else(|| artifact.custom_properties.get(name));
+            if let Some(value) = value {
+                Ok(Some(serde_json::to_string(value)?))
+            } else {
+                Ok(None)
+            }
+        } else if let Some(name) = &artifact.name {
+            Ok(Some(serde_json::to_string(&(&artifact.type_name, name))?))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+async fn get_context_artifacts(
+    store: &mut mlmd::MetadataStore,
+    context_id: i32,
+) -> anyhow::Result<Vec<Artifact>> {
+    let artifacts = store
+        .get_artifacts()
+        .context(mlmd::metadata::ContextId::new(context_id))
+        .execute()
+        .await?;
+    if artifacts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let artifact_types = store
+        .get_artifact_types()
+        .ids(
+            artifacts
+                .iter()
+                .map(|x| x.type_id)
+                .collect::<BTreeSet<_>>()
+                .into_iter(),
+        )
+        .execute()
+        .await?
+        .into_iter()
+        .map(|x| (x.id, x.name))
+        .collect::<BTreeMap<_, _>>();
+    Ok(artifacts
+        .into_iter()
+        .map(|x| Artifact::new(artifact_types[&x.type_id].clone(), x, TimeUnit::Seconds))
+        .collect())
+}
+
+/// `$ mlmdquery diff {artifact,execution}` options.
+#[derive(Debug, Clone, structopt::StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct DiffPropertiesOpt {
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
+
+    /// ID of the first item.
+    #[structopt(long)]
+    pub a: i32,
+
+    /// ID of the second item.
+    #[structopt(long)]
+    pub b: i32,
+}
+
+impl DiffPropertiesOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery diff artifact --a 1 --b 2\n",
+        "    $ mlmdquery diff execution --a 3 --b 4\n",
+    );
+
+    /// `$ mlmdquery diff artifact` implementation.
+    pub async fn diff_artifacts(&self) -> anyhow::Result<PropertiesDiff> {
+        let mut store = self.db.connect().await?;
+        let mut properties = Vec::new();
+        for id in [self.a, self.b] {
+            let mut artifacts = store
+                .get_artifacts()
+                .id(ArtifactId::new(id))
+                .execute()
+                .await?;
+            anyhow::ensure!(
+                artifacts.len() == 1,
+                NotFoundError::new(format!("No such artifact: {}", id))
+            );
+            let x = artifacts.remove(0);
+            properties.push((x.properties, x.custom_properties));
+        }
+        Ok(self.diff(properties))
+    }
+
+    /// `$ mlmdquery diff execution` implementation.
+    pub async fn diff_executions(&self) -> anyhow::Result<PropertiesDiff> {
+        let mut store = self.db.connect().await?;
+        let mut properties = Vec::new();
+        for id in [self.a, self.b] {
+            let mut executions = store
+                .get_executions()
+                .id(ExecutionId::new(id))
+                .execute()
+                .await?;
+            anyhow::ensure!(
+                executions.len() == 1,
+                NotFoundError::new(format!("No such execution: {}", id))
+            );
+            let x = executions.remove(0);
+            properties.push((x.properties, x.custom_properties));
+        }
+        Ok(self.diff(properties))
+    }
+
+    fn diff(&self, properties: Vec<(PropertyValues, PropertyValues)>) -> PropertiesDiff {
+        let convert = |x: &PropertyValues| {
+            x.iter()
+                .map(|(k, v)| (k.clone(), PropertyValue::from(v.clone())))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let (a, a_custom) = &properties[0];
+        let (b, b_custom) = &properties[1];
+        PropertiesDiff {
+            a: self.a,
+            b: self.b,
+            properties: PropertyMapDiff::new(&convert(a), &convert(b)),
+            custom_properties: PropertyMapDiff::new(&convert(a_custom), &convert(b_custom)),
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Classification of the errors reported by `mlmdquery`.
+use mlmd::errors::{GetError, InitError};
+
+/// Kind of an error.
+///
+/// This is intended to be used by programs consuming `--error-format json` output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub enum ErrorKind {
+    /// A requested item (e.g., a type) was not found in the DB.
+    NotFound,
+
+    /// Could not connect to the DB (including connection timeouts).
+    ConnectionError,
+
+    /// The DB rejected a query.
+    InvalidArgument,
+
+    /// Other errors.
+    Other,
+}
+
+/// Error report written to stderr when `--error-format json` is specified.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ErrorReport {
+    /// Kind of the error.
+    pub kind: ErrorKind,
+
+    /// Type and variant name of the underlying `mlmd` error (e.g., `"GetError::NotFound"`) if any.
+    pub mlmd_error: Option<String>,
+
+    /// Error message.
+    pub message: String,
+
+    /// Messages of the causes of the error (outermost first).
+    pub causes: Vec<String>,
+}
+
+impl ErrorReport {
+    /// Makes an [`ErrorReport`] instance from an error.
+    pub fn new(error: &anyhow::Error) -> Self {
+        let (kind, mlmd_error) = classify(error);
+        Self {
+            kind,
+            mlmd_error,
+            message: error.to_string(),
+            causes: error.chain().skip(1).map(|x| x.to_string()).collect(),
+        }
+    }
+}
+
+/// Error indicating that a requested item (e.g., an artifact or a type) does not exist in the DB.
+#[derive(Debug)]
+pub struct NotFoundError {
+    message: String,
+}
+
+impl NotFoundError {
+    /// Makes a [`NotFoundError`] instance.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for NotFoundError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for NotFoundError {}
+
+fn classify(error: &anyhow::Error) -> (ErrorKind, Option<String>) {
+    for cause in error.chain() {
+        if cause.is::<NotFoundError>() {
+            return (ErrorKind::NotFound, None);
+        }
+        if let Some(e) = cause.downcast_ref::<InitError>() {
+            return (
+                ErrorKind::ConnectionError,
+                Some(format!("InitError::{}", variant_name(e))),
+            );
+        }
+        if let Some(e) = cause.downcast_ref::<GetError>() {
+            let kind = match e {
+                GetError::Db { .. } => ErrorKind::ConnectionError,
+                _ => ErrorKind::InvalidArgument,
+            };
+            return (kind, Some(format!("GetError::{}", variant_name(e))));
+        }
+        if cause.is::<tokio::time::error::Elapsed>() {
+            return (ErrorKind::ConnectionError, None);
+        }
+    }
+    (ErrorKind::Other, None)
+}
+
+// Returns the variant name of an `mlmd` error for `ErrorReport::mlmd_error`.
+//
+// The `mlmd` error types don't expose their variant names, so the `Debug` representation is used.
+fn variant_name<T: std::fmt::Debug>(e: &T) -> String {
+    let debug = format!("{:?}", e);
+    debug
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .next()
+        .unwrap_or_default()
+        .to_owned()
+}
@@ -1,44 +1,252 @@
 //! `$ mlmdquery {get,count} events` implementation.
-use crate::serialize::Event;
-use std::collections::BTreeMap;
+use crate::db::{fetch_page, DbOpt, ID_CHUNK_SIZE, PAGE_SIZE};
+use crate::graph::{CommonGraphOpt, Edge, Graph, Node, NodeId};
+use crate::interrupt::is_interrupted;
+use crate::query::{
+    explain_filters, truncate_to_limit, FilterExplanation, FilterMode, Page, TimeUnit,
+};
+use crate::serialize::{CollapsedEvent, Event, EventEdge, EventStep, EventType};
+use anyhow::Context as _;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 /// `$ mlmdquery {get,count} events` options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 #[serde(rename_all = "kebab-case")]
 pub struct CommonEventsOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    #[serde(skip)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub db: DbOpt,
 
-    /// Artifact ID relating to target events.
-    #[structopt(long)]
+    /// Artifact IDs relating to target events (the events relating to any of them are targeted).
+    #[structopt(long = "artifact")]
+    #[serde(alias = "artifact", deserialize_with = "one_or_many")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub artifacts: Vec<i32>,
+
+    /// Execution IDs relating to target events (the events relating to any of them are targeted).
+    #[structopt(long = "execution")]
+    #[serde(alias = "execution", deserialize_with = "one_or_many")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub executions: Vec<i32>,
+
+    /// Type of the artifacts relating to target events.
+    #[structopt(long, conflicts_with("artifacts"))]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub artifact: Option<i32>,
+    pub artifact_type: Option<String>,
 
-    /// Execution ID relating to target events.
-    #[structopt(long)]
+    /// Type of the executions relating to target events.
+    #[structopt(long, conflicts_with("executions"))]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub execution: Option<i32>,
+    pub execution_type: Option<String>,
 }
 
 impl CommonEventsOpt {
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        use FilterMode::*;
+
+        explain_filters(&[
+            ("--artifact", !self.artifacts.is_empty(), ServerSide),
+            ("--execution", !self.executions.is_empty(), ServerSide),
+            // Resolved into artifact/execution IDs before querying events.
+            ("--artifact-type", self.artifact_type.is_some(), ServerSide),
+            (
+                "--execution-type",
+                self.execution_type.is_some(),
+                ServerSide,
+            ),
+        ])
+    }
+
+    // Returns `None` if no events can match the options (e.g., no artifacts have the specified type).
+    async fn resolve_targets(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<Option<EventTargets>> {
+        let mut targets = EventTargets::default();
+        if let Some(x) = &self.artifact_type {
+            let artifacts = store.get_artifacts().ty(x).execute().await?;
+            if artifacts.is_empty() {
+                return Ok(None);
+            }
+            targets.artifact_ids = artifacts.into_iter().map(|x| x.id).collect();
+        }
+        if let Some(x) = &self.execution_type {
+            let executions = store.get_executions().ty(x).execute().await?;
+            if executions.is_empty() {
+                return Ok(None);
+            }
+            targets.execution_ids = executions.into_iter().map(|x| x.id).collect();
+        }
+        Ok(Some(targets))
+    }
+
     fn request<'a>(
         &self,
         store: &'a mut mlmd::MetadataStore,
+        targets: &EventTargets,
     ) -> mlmd::requests::GetEventsRequest<'a> {
         let mut request = store.get_events();
-        if let Some(x) = self.artifact {
-            request = request.artifact(mlmd::metadata::ArtifactId::new(x));
+        if !self.artifacts.is_empty() {
+            request = request.artifacts(
+                self.artifacts
+                    .iter()
+                    .copied()
+                    .map(mlmd::metadata::ArtifactId::new),
+            );
         }
-        if let Some(x) = self.execution {
-            request = request.execution(mlmd::metadata::ExecutionId::new(x));
+        if !self.executions.is_empty() {
+            request = request.executions(
+                self.executions
+                    .iter()
+                    .copied()
+                    .map(mlmd::metadata::ExecutionId::new),
+            );
+        }
+        if !targets.artifact_ids.is_empty() {
+            request = request.artifacts(targets.artifact_ids.iter().copied());
+        }
+        if !targets.execution_ids.is_empty() {
+            request = request.executions(targets.execution_ids.iter().copied());
         }
         request
     }
 }
 
+/// Direction of an event seen from its execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum EventDirection {
+    Input,
+    Output,
+}
+
+impl EventDirection {
+    pub(crate) fn of(ty: mlmd::metadata::EventType) -> Option<Self> {
+        use mlmd::metadata::EventType::*;
+
+        match ty {
+            Input | DeclaredInput | InternalInput => Some(Self::Input),
+            Output | DeclaredOutput | InternalOutput => Some(Self::Output),
+            Unknown => None,
+        }
+    }
+}
+
+// Returns the IDs of the artifacts related to the execution by the events of the given direction.
+pub(crate) async fn related_artifact_ids(
+    store: &mut mlmd::MetadataStore,
+    execution: i32,
+    direction: EventDirection,
+) -> anyhow::Result<BTreeSet<i32>> {
+    let events = store
+        .get_events()
+        .execution(mlmd::metadata::ExecutionId::new(execution))
+        .execute()
+        .await?;
+    Ok(events
+        .into_iter()
+        .filter(|x| EventDirection::of(x.ty) == Some(direction))
+        .map(|x| x.artifact_id.get())
+        .collect())
+}
+
+// Returns the IDs of the executions related to the artifact by the events of the given direction.
+pub(crate) async fn related_execution_ids(
+    store: &mut mlmd::MetadataStore,
+    artifact: i32,
+    direction: EventDirection,
+) -> anyhow::Result<BTreeSet<i32>> {
+    let events = store
+        .get_events()
+        .artifact(mlmd::metadata::ArtifactId::new(artifact))
+        .execute()
+        .await?;
+    Ok(events
+        .into_iter()
+        .filter(|x| EventDirection::of(x.ty) == Some(direction))
+        .map(|x| x.execution_id.get())
+        .collect())
+}
+
+// Summarizes event paths ending with indices into ranges per prefix (e.g., `[0]`, `[1]`, `[2]` and `[5]` into `["0-2", "5"]`).
+fn summarize_steps(paths: &[Vec<EventStep>]) -> Vec<String> {
+    let join = |steps: &[EventStep]| {
+        steps
+            .iter()
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+
+    let mut indices = BTreeMap::<_, BTreeSet<_>>::new();
+    let mut others = BTreeSet::new();
+    for path in paths {
+        match path.split_last() {
+            Some((EventStep::Index(i), prefix)) => {
+                indices.entry(join(prefix)).or_default().insert(*i);
+            }
+            Some(_) => {
+                others.insert(join(path));
+            }
+            None => {}
+        }
+    }
+
+    let mut steps = Vec::new();
+    for (prefix, indices) in indices {
+        let mut ranges = Vec::<(i32, i32)>::new();
+        for i in indices {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == i => *end = i,
+                _ => ranges.push((i, i)),
+            }
+        }
+        for (start, end) in ranges {
+            let range = if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            };
+            if prefix.is_empty() {
+                steps.push(range);
+            } else {
+                steps.push(format!("{}.{}", prefix, range));
+            }
+        }
+    }
+    steps.extend(others);
+    steps
+}
+
+// Accepts a single ID (the format before `--artifact` and `--execution` became repeatable) as well as an array.
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<i32>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(serde::Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(i32),
+        Many(Vec<i32>),
+    }
+
+    match serde::Deserialize::deserialize(deserializer)? {
+        OneOrMany::One(x) => Ok(vec![x]),
+        OneOrMany::Many(x) => Ok(x),
+    }
+}
+
+#[derive(Debug, Default)]
+struct EventTargets {
+    artifact_ids: Vec<mlmd::metadata::ArtifactId>,
+    execution_ids: Vec<mlmd::metadata::ExecutionId>,
+}
+
 /// `$ mlmdquery count events` options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
 pub struct CountEventsOpt {
@@ -51,8 +259,12 @@ pub struct CountEventsOpt {
 impl CountEventsOpt {
     /// `$ mlmdquery count events` implementation.
     pub async fn count(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<usize> {
-        let n = self.common.request(store).count().await?;
-        Ok(n)
+        if let Some(targets) = self.common.resolve_targets(store).await? {
+            let n = self.common.request(store, &targets).count().await?;
+            Ok(n)
+        } else {
+            Ok(0)
+        }
     }
 }
 
@@ -65,8 +277,8 @@ pub struct GetEventsOpt {
     #[serde(flatten)]
     pub common: CommonEventsOpt,
 
-    /// Maximum number of artifacts in a search result.
-    #[structopt(long, default_value = "100")]
+    /// Maximum number of artifacts in a search result (0 means unlimited).
+    #[structopt(long, env = "MLMD_LIMIT_EVENTS", default_value = "100")]
     #[serde(default = "GetEventsOpt::limit_default")]
     pub limit: usize,
 
@@ -79,31 +291,131 @@ pub struct GetEventsOpt {
     #[structopt(long)]
     #[serde(default)]
     pub asc: bool,
+
+    /// If specified, the search results will be sorted in descending order (default).
+    #[structopt(long, conflicts_with("asc"))]
+    #[serde(default)]
+    pub desc: bool,
+
+    /// Unit of the event timestamps (`time`) in the output.
+    #[structopt(long, default_value = "s", possible_values = TimeUnit::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub time_unit: TimeUnit,
+
+    /// File that keeps track of the last exported event (created if it does not exist).
+    ///
+    /// If specified, only the events newer than the ones recorded in the file are fetched
+    /// (in ascending order of creation time) and the file is updated to point to the last fetched event.
+    /// `--offset`, `--asc` and `--desc` are ignored in this mode.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cursor_file: Option<PathBuf>,
+
+    /// Output format of the events.
+    ///
+    /// `edge-list` outputs each event as a directed edge (`from_id`, `to_id` and `event_type`)
+    /// between the artifact and the execution, in the same direction as the `graph` commands.
+    #[structopt(long, default_value = "full", possible_values = EventFormat::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub format: EventFormat,
+
+    /// If specified, the events having the same artifact, execution and type are collapsed into one entry.
+    ///
+    /// The paths of the collapsed events are summarized in `steps` (e.g., `["0-9", "model.0-1"]`),
+    /// `count` is the number of the events and `time` is that of the first one in the result order.
+    /// Note that this is applied after `--limit` and `--offset`.
+    #[structopt(long)]
+    #[serde(default)]
+    pub collapse_steps: bool,
+
+    /// If specified, the fetched events are drawn as a graph in DOT language instead of being output as JSON.
+    ///
+    /// The artifacts and executions referred to by the events become the nodes of the graph.
+    /// This cannot be used in `$ mlmdquery batch`.
+    #[structopt(long)]
+    #[serde(default)]
+    pub as_graph: bool,
+}
+
+/// Output format of `$ mlmdquery get events`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+#[allow(missing_docs)]
+pub enum EventFormat {
+    #[default]
+    Full,
+    EdgeList,
+}
+
+impl EventFormat {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["full", "edge-list"];
+}
+
+impl std::str::FromStr for EventFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "full" => Ok(Self::Full),
+            "edge-list" => Ok(Self::EdgeList),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
 }
 
 impl GetEventsOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery get events --artifact 1\n",
+        "    $ mlmdquery get events --artifact 1 --artifact 2\n",
+        "    $ mlmdquery get events --execution 1 --asc\n",
+        "    $ mlmdquery get events --artifact-type DataSet --limit 0\n",
+        "    $ mlmdquery get events --cursor-file cursor.json --limit 0\n",
+        "    $ mlmdquery get events --format edge-list --output edges.csv\n",
+        "    $ mlmdquery get events --execution 1 --collapse-steps\n",
+        "    $ mlmdquery get events --artifact-type Model --limit 0 --as-graph | dot -Tsvg > events.svg\n",
+    );
+
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        let mut filters = self.common.explain();
+        filters.extend(explain_filters(&[(
+            "--cursor-file",
+            self.cursor_file.is_some(),
+            FilterMode::ClientSide,
+        )]));
+        filters
+    }
+
     fn limit_default() -> usize {
-        100
+        std::env::var("MLMD_LIMIT_EVENTS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100)
     }
 
     /// `$ mlmdquery get events` implementation.
-    pub async fn get(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Vec<Event>> {
-        let events = self
-            .common
-            .request(store)
-            .limit(self.limit)
-            .offset(self.offset)
-            .order_by(mlmd::requests::EventOrderByField::CreateTime, self.asc)
-            .execute()
-            .await?;
-
+    ///
+    /// If `--cursor-file` is specified, the advanced cursor is returned instead of being saved,
+    /// so that the caller can save it after the events are successfully written.
+    pub async fn get(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<(Page<Event>, Option<PendingCursor>)> {
+        let (
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ) = self.fetch(store).await?;
         let artifact_types = self
             .get_artifact_types(store, events.iter().map(|x| x.artifact_id))
             .await?;
         let execution_types = self
             .get_execution_types(store, events.iter().map(|x| x.execution_id))
             .await?;
-        Ok(events
+        let events = events
             .into_iter()
             .map(|x| Event {
                 artifact: x.artifact_id.get(),
@@ -112,9 +424,225 @@ impl GetEventsOpt {
                 execution_type: execution_types[&x.execution_id].clone(),
                 event_type: x.ty.into(),
                 path: x.path.into_iter().map(From::from).collect(),
-                time: x.create_time_since_epoch.as_secs_f64(),
+                time: self.time_unit.timestamp(x.create_time_since_epoch),
             })
-            .collect())
+            .collect();
+        Ok((
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ))
+    }
+
+    /// `$ mlmdquery get events --collapse-steps` implementation.
+    pub async fn get_collapsed(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<(Page<CollapsedEvent>, Option<PendingCursor>)> {
+        let (
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ) = self.get(store).await?;
+        let mut indices = HashMap::<_, usize>::new();
+        let mut groups = Vec::<(Event, Vec<Vec<EventStep>>)>::new();
+        for mut event in events {
+            let key = (event.artifact, event.execution, event.event_type);
+            match indices.entry(key) {
+                Entry::Occupied(entry) => groups[*entry.get()].1.push(event.path),
+                Entry::Vacant(entry) => {
+                    entry.insert(groups.len());
+                    let path = std::mem::take(&mut event.path);
+                    groups.push((event, vec![path]));
+                }
+            }
+        }
+        let events = groups
+            .into_iter()
+            .map(|(x, paths)| CollapsedEvent {
+                artifact: x.artifact,
+                artifact_type: x.artifact_type,
+                execution: x.execution,
+                execution_type: x.execution_type,
+                event_type: x.event_type,
+                steps: summarize_steps(&paths),
+                count: paths.len(),
+                time: x.time,
+            })
+            .collect();
+        Ok((
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ))
+    }
+
+    /// `$ mlmdquery get events --as-graph` implementation.
+    pub async fn graph<W: Write>(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        writer: &mut W,
+    ) -> anyhow::Result<()> {
+        let (Page { items: events, .. }, cursor) = self.fetch(store).await?;
+        let artifact_ids = events
+            .iter()
+            .map(|x| x.artifact_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let execution_ids = events
+            .iter()
+            .map(|x| x.execution_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut nodes = HashMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let artifacts = store
+                .get_artifacts()
+                .ids(chunk.iter().copied())
+                .execute()
+                .await?;
+            for x in artifacts {
+                nodes.insert(NodeId::Artifact(x.id), Node::Artifact(x));
+            }
+        }
+        for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+            let executions = store
+                .get_executions()
+                .ids(chunk.iter().copied())
+                .execute()
+                .await?;
+            for x in executions {
+                nodes.insert(NodeId::Execution(x.id), Node::Execution(x));
+            }
+        }
+        let edges = events.into_iter().map(Edge::new).collect();
+
+        let mut graph =
+            Graph::new(store, Vec::new(), nodes, edges, CommonGraphOpt::default()).await?;
+        graph.prune_dangling_edges();
+        graph.generate(writer)?;
+        if let Some(cursor) = cursor {
+            cursor.save()?;
+        }
+        Ok(())
+    }
+
+    /// `$ mlmdquery get events --format edge-list` implementation.
+    pub async fn get_edges(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<(Page<EventEdge>, Option<PendingCursor>)> {
+        let (
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ) = self.fetch(store).await?;
+        let edges = events
+            .into_iter()
+            .map(|x| {
+                let event_type = x.ty.into();
+                let edge = Edge::new(x);
+                EventEdge {
+                    from_id: edge.from_node().to_string(),
+                    to_id: edge.to_node().to_string(),
+                    event_type,
+                }
+            })
+            .collect();
+        Ok((
+            Page {
+                items: edges,
+                truncated,
+            },
+            cursor,
+        ))
+    }
+
+    async fn fetch(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<(Page<mlmd::metadata::Event>, Option<PendingCursor>)> {
+        let targets = if let Some(targets) = self.common.resolve_targets(store).await? {
+            targets
+        } else {
+            let page = Page {
+                items: Vec::new(),
+                truncated: false,
+            };
+            return Ok((page, None));
+        };
+        if let Some(path) = &self.cursor_file {
+            let (events, cursor) = self.get_since_cursor(store, &targets, path).await?;
+            return Ok((events, Some(cursor)));
+        }
+
+        let events = fetch_page(store, self.limit, self.offset, |store, limit, offset| {
+            let request = self
+                .common
+                .request(store, &targets)
+                .limit(limit)
+                .offset(offset)
+                .order_by(mlmd::requests::EventOrderByField::CreateTime, self.asc);
+            Box::pin(async move { Ok(request.execute().await?) })
+        })
+        .await?;
+        Ok((events, None))
+    }
+
+    // Fetches the events created at or after the time of the cursor (in ascending order of creation time).
+    //
+    // Only the events sharing the cursor time are filtered on the client side.
+    async fn get_since_cursor(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        targets: &EventTargets,
+        path: &Path,
+    ) -> anyhow::Result<(Page<mlmd::metadata::Event>, PendingCursor)> {
+        let mut cursor = EventCursor::load(path)?;
+        let mut events = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self
+                .common
+                .request(store, targets)
+                .create_time(cursor.time..)
+                .limit(PAGE_SIZE)
+                .offset(offset)
+                .order_by(mlmd::requests::EventOrderByField::CreateTime, true)
+                .execute()
+                .await?;
+            offset += page.len();
+            let done = page.len() < PAGE_SIZE;
+            events.extend(page.into_iter().filter(|x| cursor.is_new(x)));
+            if done || is_interrupted() || (self.limit != 0 && events.len() > self.limit) {
+                break;
+            }
+        }
+        let truncated = truncate_to_limit(&mut events, self.limit);
+
+        cursor.advance(&events);
+        let cursor = PendingCursor {
+            path: path.to_owned(),
+            cursor,
+        };
+        Ok((
+            Page {
+                items: events,
+                truncated,
+            },
+            cursor,
+        ))
     }
 
     async fn get_artifact_types(
@@ -122,17 +650,28 @@ impl GetEventsOpt {
         store: &mut mlmd::MetadataStore,
         artifact_ids: impl Iterator<Item = mlmd::metadata::ArtifactId>,
     ) -> anyhow::Result<BTreeMap<mlmd::metadata::ArtifactId, String>> {
-        let artifacts = store
-            .get_artifacts()
-            .ids(artifact_ids)
-            .execute()
-            .await?
+        let artifact_ids = artifact_ids
+            .collect::<BTreeSet<_>>()
             .into_iter()
-            .map(|x| (x.id, x.type_id))
-            .collect::<BTreeMap<_, _>>();
+            .collect::<Vec<_>>();
+        let mut artifacts = BTreeMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let found = store
+                .get_artifacts()
+                .ids(chunk.iter().copied())
+                .execute()
+                .await?;
+            artifacts.extend(found.into_iter().map(|x| (x.id, x.type_id)));
+        }
         let artifact_types = store
             .get_artifact_types()
-            .ids(artifacts.values().copied())
+            .ids(
+                artifacts
+                    .values()
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter(),
+            )
             .execute()
             .await?
             .into_iter()
@@ -149,17 +688,28 @@ impl GetEventsOpt {
         store: &mut mlmd::MetadataStore,
         execution_ids: impl Iterator<Item = mlmd::metadata::ExecutionId>,
     ) -> anyhow::Result<BTreeMap<mlmd::metadata::ExecutionId, String>> {
-        let executions = store
-            .get_executions()
-            .ids(execution_ids)
-            .execute()
-            .await?
+        let execution_ids = execution_ids
+            .collect::<BTreeSet<_>>()
             .into_iter()
-            .map(|x| (x.id, x.type_id))
-            .collect::<BTreeMap<_, _>>();
+            .collect::<Vec<_>>();
+        let mut executions = BTreeMap::new();
+        for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+            let found = store
+                .get_executions()
+                .ids(chunk.iter().copied())
+                .execute()
+                .await?;
+            executions.extend(found.into_iter().map(|x| (x.id, x.type_id)));
+        }
         let execution_types = store
             .get_execution_types()
-            .ids(executions.values().copied())
+            .ids(
+                executions
+                    .values()
+                    .copied()
+                    .collect::<BTreeSet<_>>()
+                    .into_iter(),
+            )
             .execute()
             .await?
             .into_iter()
@@ -171,3 +721,90 @@ impl GetEventsOpt {
             .collect())
     }
 }
+
+/// Advanced `--cursor-file` position that has not been saved yet.
+///
+/// This should be saved only after the fetched events are successfully written,
+/// otherwise the events would be skipped in the next run.
+#[derive(Debug)]
+pub struct PendingCursor {
+    path: PathBuf,
+    cursor: EventCursor,
+}
+
+impl PendingCursor {
+    /// Saves the cursor to the `--cursor-file`.
+    pub fn save(&self) -> anyhow::Result<()> {
+        self.cursor.save(&self.path)
+    }
+}
+
+/// Contents of a `--cursor-file`.
+///
+/// Events have no IDs, so the events sharing the creation time of the last exported event
+/// are recorded to avoid exporting them twice.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+struct EventCursor {
+    time: Duration,
+    events: Vec<EventKey>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct EventKey {
+    artifact: i32,
+    execution: i32,
+    #[serde(rename = "type")]
+    event_type: EventType,
+}
+
+impl EventKey {
+    fn new(event: &mlmd::metadata::Event) -> Self {
+        Self {
+            artifact: event.artifact_id.get(),
+            execution: event.execution_id.get(),
+            event_type: event.ty.into(),
+        }
+    }
+}
+
+impl EventCursor {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("invalid cursor file: {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("failed to read {}", path.display())),
+        }
+    }
+
+    // Writes to a temporary file first and then renames it, so that the cursor is never half-written.
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        let tmp = PathBuf::from(tmp);
+        std::fs::write(&tmp, serde_json::to_vec(self)?)
+            .with_context(|| format!("failed to write {}", tmp.display()))?;
+        std::fs::rename(&tmp, path)
+            .with_context(|| format!("failed to rename {} to {}", tmp.display(), path.display()))?;
+        Ok(())
+    }
+
+    fn is_new(&self, event: &mlmd::metadata::Event) -> bool {
+        match event.create_time_since_epoch.cmp(&self.time) {
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => !self.events.contains(&EventKey::new(event)),
+            std::cmp::Ordering::Greater => true,
+        }
+    }
+
+    // `events` must be sorted in ascending order of creation time.
+    fn advance(&mut self, events: &[mlmd::metadata::Event]) {
+        for event in events {
+            if event.create_time_since_epoch > self.time {
+                self.time = event.create_time_since_epoch;
+                self.events.clear();
+            }
+            self.events.push(EventKey::new(event));
+        }
+    }
+}
@@ -1,17 +1,26 @@
 //! `$ mlmdquery {get,count} executions` implementation.
-use crate::serialize::Execution;
+use crate::db::{fetch_all_pages, fetch_page, DbOpt, ID_CHUNK_SIZE};
+use crate::events::{related_execution_ids, EventDirection};
+use crate::graph::TimeZone;
+use crate::query::{
+    count_needs_fetch, dedup_ids, explain_filters, paginate, shuffle, sort_by_id_order,
+    FilterExplanation, FilterMode, NonFinitePolicy, Page, PropertyFilter, TimePoint, TimeUnit,
+};
+use crate::serialize::{
+    coerce_properties, handle_nonfinite, state_name, Artifact, Execution, ExecutionState, Type,
+};
+use anyhow::Context as _;
 use std::collections::{BTreeMap, BTreeSet};
-use std::time::Duration;
 
 /// `$ mlmdquery {get,count} executions` common options.
 #[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
 #[structopt(rename_all = "kebab-case")]
 #[structopt(rename_all = "kebab-case")]
 pub struct CommonExecutionsOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    #[serde(skip)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    #[serde(flatten)]
+    pub db: DbOpt,
 
     /// Target execution IDs.
     #[structopt(long = "id")]
@@ -39,39 +48,171 @@ pub struct CommonExecutionsOpt {
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub context: Option<i32>,
 
-    /// Start of creation time (UNIX timestamp seconds).
+    /// Artifact ID produced by target executions (i.e., via `OUTPUT` events).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub produced: Option<i32>,
+
+    /// Artifact ID consumed by target executions (i.e., via `INPUT` events).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_start: Option<f64>,
+    pub consumed: Option<i32>,
 
-    /// End of creation time (UNIX timestamp seconds).
+    /// Start of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub ctime_end: Option<f64>,
+    pub ctime_start: Option<TimePoint>,
 
-    /// Start of update time (UNIX timestamp seconds).
+    /// End of creation time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_start: Option<f64>,
+    pub ctime_end: Option<TimePoint>,
 
-    /// End of update time (UNIX timestamp seconds).
+    /// Start of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
     #[structopt(long)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
-    pub mtime_end: Option<f64>,
+    pub mtime_start: Option<TimePoint>,
+
+    /// End of update time (UNIX timestamp seconds or date string such as `2021-08-10`).
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime_end: Option<TimePoint>,
+
+    /// Time zone in which the date strings given to `--{c,m}time-{start,end}` are interpreted
+    /// (unless they contain an offset).
+    #[structopt(long, default_value = "utc", possible_values = TimeZone::POSSIBLE_VALUES, case_insensitive = true)]
+    #[serde(default = "TimeZone::utc")]
+    pub input_timezone: TimeZone,
+
+    /// Target execution custom property values (`NAME=VALUE`, can be specified multiple times).
+    ///
+    /// Only the executions having all the given values in `custom_properties` are returned.
+    /// VALUE is parsed as an int, a double or a string (in that order), and VALUE prefixed with `@`
+    /// is read from the file at the following path.
+    ///
+    /// Note that this filter is applied after fetching executions from the DB.
+    #[structopt(long = "custom-property")]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub custom_properties: Vec<PropertyFilter>,
+
+    // Set by `get executions --failed-inputs` to match only FAILED executions.
+    #[structopt(skip)]
+    #[serde(skip)]
+    failed_only: bool,
 }
 
 impl CommonExecutionsOpt {
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        use FilterMode::*;
+
+        explain_filters(&[
+            ("--id", !self.ids.is_empty(), ServerSide),
+            ("--name", self.name.is_some(), ServerSide),
+            ("--name-pattern", self.name_pattern.is_some(), ServerSide),
+            ("--type", self.type_name.is_some(), ServerSide),
+            ("--context", self.context.is_some(), ServerSide),
+            // Resolved into execution IDs before querying executions.
+            ("--produced", self.produced.is_some(), ServerSide),
+            ("--consumed", self.consumed.is_some(), ServerSide),
+            ("--ctime-start", self.ctime_start.is_some(), ServerSide),
+            ("--ctime-end", self.ctime_end.is_some(), ServerSide),
+            ("--mtime-start", self.mtime_start.is_some(), ServerSide),
+            ("--mtime-end", self.mtime_end.is_some(), ServerSide),
+            (
+                "--custom-property",
+                !self.custom_properties.is_empty(),
+                ClientSide,
+            ),
+            ("--failed-inputs", self.failed_only, ClientSide),
+        ])
+    }
+
+    fn has_client_side_filters(&self) -> bool {
+        self.explain()
+            .iter()
+            .any(|x| x.mode != FilterMode::ServerSide)
+    }
+
+    fn matches(&self, execution: &mlmd::metadata::Execution) -> bool {
+        if self.failed_only
+            && !matches!(
+                execution.last_known_state,
+                mlmd::metadata::ExecutionState::Failed
+            )
+        {
+            return false;
+        }
+        self.custom_properties
+            .iter()
+            .all(|x| x.matches(&execution.custom_properties))
+    }
+
+    // Fetches all the executions that match the options, `PAGE_SIZE` executions at a time.
+    async fn execute(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<Vec<mlmd::metadata::Execution>> {
+        let mut executions = fetch_all_pages(store, 0, 0, |store, limit, offset| {
+            let request = self.request(store).map(|x| {
+                x.limit(limit)
+                    .offset(offset)
+                    .order_by(ExecutionOrderByField::Id.into(), true)
+            });
+            Box::pin(async move { Ok(request?.execute().await?) })
+        })
+        .await?;
+        executions.retain(|x| self.matches(x));
+        Ok(executions)
+    }
+
+    // Returns a copy of the options in which `--produced` / `--consumed` are replaced with
+    // the execution IDs they refer to.
+    //
+    // Returns `None` if no executions can match the options.
+    async fn resolve(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Option<Self>> {
+        if self.produced.is_none() && self.consumed.is_none() {
+            return Ok(Some(self.clone()));
+        }
+
+        let mut candidates = if self.ids.is_empty() {
+            None
+        } else {
+            Some(self.ids.iter().copied().collect::<BTreeSet<_>>())
+        };
+        for (artifact, direction) in [
+            (self.produced, EventDirection::Output),
+            (self.consumed, EventDirection::Input),
+        ] {
+            if let Some(artifact) = artifact {
+                let ids = related_execution_ids(store, artifact, direction).await?;
+                candidates = Some(match candidates {
+                    None => ids,
+                    Some(x) => x.intersection(&ids).copied().collect(),
+                });
+            }
+        }
+        match candidates {
+            Some(x) if !x.is_empty() => {
+                let mut resolved = self.clone();
+                resolved.ids = x.into_iter().collect();
+                resolved.produced = None;
+                resolved.consumed = None;
+                Ok(Some(resolved))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn request<'a>(
         &self,
         store: &'a mut mlmd::MetadataStore,
-    ) -> mlmd::requests::GetExecutionsRequest<'a> {
+    ) -> anyhow::Result<mlmd::requests::GetExecutionsRequest<'a>> {
         let mut request = store.get_executions();
 
         if !self.ids.is_empty() {
             request = request.ids(
-                self.ids
-                    .iter()
-                    .copied()
+                dedup_ids(&self.ids)
+                    .into_iter()
                     .map(mlmd::metadata::ExecutionId::new),
             );
         }
@@ -90,24 +231,25 @@ impl CommonExecutionsOpt {
         if let Some(x) = self.context {
             request = request.context(mlmd::metadata::ContextId::new(x));
         }
-        request = match (self.ctime_start, self.ctime_end) {
+        let time = |x: &Option<TimePoint>| {
+            x.as_ref()
+                .map(|x| x.to_duration(self.input_timezone))
+                .transpose()
+        };
+        request = match (time(&self.ctime_start)?, time(&self.ctime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.create_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.create_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.create_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.create_time(s..),
+            (None, Some(e)) => request.create_time(..e),
+            (Some(s), Some(e)) => request.create_time(s..e),
         };
-        request = match (self.mtime_start, self.mtime_end) {
+        request = match (time(&self.mtime_start)?, time(&self.mtime_end)?) {
             (None, None) => request,
-            (Some(s), None) => request.update_time(Duration::from_secs_f64(s)..),
-            (None, Some(e)) => request.update_time(..Duration::from_secs_f64(e)),
-            (Some(s), Some(e)) => {
-                request.update_time(Duration::from_secs_f64(s)..Duration::from_secs_f64(e))
-            }
+            (Some(s), None) => request.update_time(s..),
+            (None, Some(e)) => request.update_time(..e),
+            (Some(s), Some(e)) => request.update_time(s..e),
         };
 
-        request
+        Ok(request)
     }
 }
 
@@ -122,10 +264,21 @@ pub enum ExecutionOrderByField {
     CreateTime,
     #[serde(rename = "mtime")]
     UpdateTime,
+    Random,
 }
 
 impl ExecutionOrderByField {
-    const POSSIBLE_VALUES: &'static [&'static str] = &["id", "name", "ctime", "mtime"];
+    const POSSIBLE_VALUES: &'static [&'static str] = &["id", "name", "ctime", "mtime", "random"];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Name => "name",
+            Self::CreateTime => "ctime",
+            Self::UpdateTime => "mtime",
+            Self::Random => "random",
+        }
+    }
 }
 
 impl Default for ExecutionOrderByField {
@@ -143,6 +296,7 @@ impl std::str::FromStr for ExecutionOrderByField {
             "name" => Ok(Self::Name),
             "ctime" => Ok(Self::CreateTime),
             "mtime" => Ok(Self::UpdateTime),
+            "random" => Ok(Self::Random),
             _ => anyhow::bail!("invalid value: {:?}", s),
         }
     }
@@ -155,6 +309,8 @@ impl From<ExecutionOrderByField> for mlmd::requests::ExecutionOrderByField {
             ExecutionOrderByField::Name => Self::Name,
             ExecutionOrderByField::CreateTime => Self::CreateTime,
             ExecutionOrderByField::UpdateTime => Self::UpdateTime,
+            // The DB cannot randomize the order, so the result is shuffled on the client side.
+            ExecutionOrderByField::Random => Self::Id,
         }
     }
 }
@@ -166,14 +322,49 @@ pub struct CountExecutionsOpt {
     #[structopt(flatten)]
     #[serde(flatten)]
     pub common: CommonExecutionsOpt,
+
+    /// If specified, the executions are counted per state (`{"COMPLETE": N, "FAILED": M, ...}`).
+    #[structopt(long)]
+    #[serde(default)]
+    pub count_by_state: bool,
 }
 
 impl CountExecutionsOpt {
     /// `$ mlmdquery count executions` implementation.
     pub async fn count(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<usize> {
-        let n = self.common.request(store).count().await?;
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(0);
+        };
+        if count_needs_fetch(&common.explain()) {
+            let executions = common.execute(store).await?;
+            return Ok(executions.len());
+        }
+        let n = common.request(store)?.count().await?;
         Ok(n)
     }
+
+    /// `$ mlmdquery count executions --count-by-state` implementation.
+    pub async fn count_states(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<BTreeMap<String, usize>> {
+        let mut counts = BTreeMap::new();
+        let common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(counts);
+        };
+        // The states are always counted on the client side, so this is only for the warning about client-side filters.
+        count_needs_fetch(&common.explain());
+        let executions = common.execute(store).await?;
+        for execution in &executions {
+            let state = state_name(ExecutionState::from(execution.last_known_state));
+            *counts.entry(state).or_default() += 1;
+        }
+        Ok(counts)
+    }
 }
 
 /// `$ mlmdquery get executions` options.
@@ -186,17 +377,39 @@ pub struct GetExecutionsOpt {
     pub common: CommonExecutionsOpt,
 
     /// Field to be used to sort a search result.
+    ///
+    /// `random` shuffles the result on the client side, so all the matching executions are fetched
+    /// from the DB before `--limit` and `--offset` are applied (this may be slow for a large DB).
     #[structopt(long, default_value="id", possible_values = ExecutionOrderByField::POSSIBLE_VALUES)]
     #[serde(default)]
     pub order_by: ExecutionOrderByField,
 
+    /// Seed of the random number generator used by `--order-by random`.
+    ///
+    /// If omitted, a different order is produced on each run.
+    #[structopt(long)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+
+    /// If specified, the search results are sorted in the order of the `--id` arguments (overriding `--order-by`).
+    ///
+    /// Note that all the matching executions are fetched from the DB before `--limit` and `--offset` are applied.
+    #[structopt(long, requires("ids"))]
+    #[serde(default)]
+    pub preserve_order: bool,
+
     /// If specified, the search results will be sorted in ascending order.
     #[structopt(long)]
     #[serde(default)]
     pub asc: bool,
 
-    /// Maximum number of artifacts in a search result.
-    #[structopt(long, default_value = "100")]
+    /// If specified, the search results will be sorted in descending order (default).
+    #[structopt(long, conflicts_with("asc"))]
+    #[serde(default)]
+    pub desc: bool,
+
+    /// Maximum number of artifacts in a search result (0 means unlimited).
+    #[structopt(long, env = "MLMD_LIMIT_EXECUTIONS", default_value = "100")]
     #[serde(default = "GetExecutionsOpt::limit_default")]
     pub limit: usize,
 
@@ -204,53 +417,382 @@ pub struct GetExecutionsOpt {
     #[structopt(long, default_value = "0")]
     #[serde(default)]
     pub offset: usize,
+
+    /// If specified, `properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_properties: bool,
+
+    /// If specified, `custom_properties` will be omitted from the output.
+    #[structopt(long)]
+    #[serde(default)]
+    pub no_custom_properties: bool,
+
+    /// If specified, only FAILED executions are returned together with their input artifacts.
+    #[structopt(long)]
+    #[serde(default)]
+    pub failed_inputs: bool,
+
+    /// If specified, only root executions (none of whose input artifacts was output by another execution) are returned.
+    #[structopt(long)]
+    #[serde(default)]
+    pub roots: bool,
+
+    /// If specified, only leaf executions (none of whose output artifacts was input to another execution) are returned.
+    #[structopt(long)]
+    #[serde(default)]
+    pub leaves: bool,
+
+    /// If specified, `duration_seconds` (from the first input event to the last output event) is added to each execution.
+    #[structopt(long)]
+    #[serde(default)]
+    pub with_duration: bool,
+
+    /// If specified, property values are converted to the types declared in their type schema.
+    #[structopt(long)]
+    #[serde(default)]
+    pub coerce_to_schema: bool,
+
+    /// If specified, property values that cannot be converted to their declared types cause an error.
+    #[structopt(long, requires("coerce-to-schema"))]
+    #[serde(default)]
+    pub strict: bool,
+
+    /// Unit of the timestamps (`ctime` and `mtime`) in the output.
+    #[structopt(long, default_value = "s", possible_values = TimeUnit::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub time_unit: TimeUnit,
+
+    /// How to output non-finite (`NaN` or infinite) double property values.
+    ///
+    /// `null` outputs `null`, `string` outputs "NaN", "Infinity" or "-Infinity", and `error` fails the query.
+    #[structopt(long, default_value = "null", possible_values = NonFinitePolicy::POSSIBLE_VALUES)]
+    #[serde(default)]
+    pub nonfinite: NonFinitePolicy,
 }
 
 impl GetExecutionsOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery get executions --type Trainer\n",
+        "    $ mlmdquery get executions --context 1 --order-by ctime --asc\n",
+        "    $ mlmdquery get executions --mtime-start 1628605220 --with-duration\n",
+        "    $ mlmdquery get executions --produced 2\n",
+        "    $ mlmdquery get executions --failed-inputs --limit 0\n",
+        "    $ mlmdquery get executions --context 1 --roots --limit 0\n",
+        "    $ mlmdquery get executions --type Trainer --order-by random --seed 42 --limit 5\n",
+    );
+
+    pub(crate) fn explain(&self) -> Vec<FilterExplanation> {
+        let mut filters = self.common.explain();
+        filters.extend(explain_filters(&[
+            (
+                "--order-by random",
+                matches!(self.order_by, ExecutionOrderByField::Random),
+                FilterMode::ClientSide,
+            ),
+            (
+                "--preserve-order",
+                self.preserve_order,
+                FilterMode::ClientSide,
+            ),
+            (
+                "--failed-inputs",
+                self.failed_inputs,
+                FilterMode::ClientSide,
+            ),
+            ("--roots", self.roots, FilterMode::ClientSideAfterLimit),
+            ("--leaves", self.leaves, FilterMode::ClientSideAfterLimit),
+        ]));
+        filters
+    }
+
     fn limit_default() -> usize {
-        100
+        std::env::var("MLMD_LIMIT_EXECUTIONS")
+            .ok()
+            .and_then(|x| x.parse().ok())
+            .unwrap_or(100)
     }
 
     /// `$ mlmdquery get executions` implementation.
     pub async fn get(&self, store: &mut mlmd::MetadataStore) -> anyhow::Result<Vec<Execution>> {
-        let executions = self
-            .common
-            .request(store)
-            .limit(self.limit)
-            .offset(self.offset)
-            .order_by(self.order_by.into(), self.asc)
-            .execute()
-            .await?;
+        Ok(self.get_page(store).await?.items)
+    }
+
+    /// Same as [`GetExecutionsOpt::get`], but also tells whether more executions than `--limit` existed.
+    pub async fn get_page(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<Page<Execution>> {
+        let mut common = if let Some(common) = self.common.resolve(store).await? {
+            common
+        } else {
+            return Ok(Page {
+                items: Vec::new(),
+                truncated: false,
+            });
+        };
+        common.failed_only = self.failed_inputs;
+        let random = matches!(self.order_by, ExecutionOrderByField::Random);
+        let client_side = random || self.preserve_order || common.has_client_side_filters();
+        let (limit, offset) = if client_side {
+            (0, 0)
+        } else {
+            (self.limit, self.offset)
+        };
+        let Page {
+            items: mut executions,
+            mut truncated,
+        } = fetch_page(store, limit, offset, |store, limit, offset| {
+            let request = common.request(store).map(|x| {
+                x.limit(limit)
+                    .offset(offset)
+                    .order_by(self.order_by.into(), self.asc)
+            });
+            Box::pin(async move { Ok(request?.execute().await?) })
+        })
+        .await?;
+        executions.retain(|x| common.matches(x));
+        if random {
+            shuffle(&mut executions, self.seed);
+        }
+        if self.preserve_order {
+            sort_by_id_order(&mut executions, &self.common.ids, |x| x.id.get());
+        }
+        if client_side {
+            let page = paginate(executions, self.offset, self.limit);
+            executions = page.items;
+            truncated = page.truncated;
+        }
+        if self.roots || self.leaves {
+            self.retain_roots_and_leaves(store, &mut executions).await?;
+        }
 
         let execution_types = self.get_execution_types(store, &executions).await?;
-        Ok(executions
+        let mut executions = executions
             .into_iter()
-            .map(|x| Execution {
-                id: x.id.get(),
-                name: x.name,
-                type_name: execution_types[&x.type_id].clone(),
-                state: x.last_known_state.into(),
-                ctime: x.create_time_since_epoch.as_secs_f64(),
-                mtime: x.last_update_time_since_epoch.as_secs_f64(),
-                properties: x
-                    .properties
-                    .into_iter()
-                    .map(|(k, v)| (k, v.into()))
-                    .collect(),
-                custom_properties: x
-                    .custom_properties
-                    .into_iter()
-                    .map(|(k, v)| (k, v.into()))
-                    .collect(),
+            .map(|mut x| {
+                if self.no_properties {
+                    x.properties.clear();
+                }
+                if self.no_custom_properties {
+                    x.custom_properties.clear();
+                }
+                let ty = &execution_types[&x.type_id];
+                let mut execution = Execution {
+                    id: x.id.get(),
+                    name: x.name,
+                    type_name: ty.name.clone(),
+                    state: x.last_known_state.into(),
+                    ctime: self.time_unit.timestamp(x.create_time_since_epoch),
+                    mtime: self.time_unit.timestamp(x.last_update_time_since_epoch),
+                    properties: x
+                        .properties
+                        .into_iter()
+                        .map(|(k, v)| (k, v.into()))
+                        .collect(),
+                    custom_properties: x
+                        .custom_properties
+                        .into_iter()
+                        .map(|(k, v)| (k, v.into()))
+                        .collect(),
+                    inputs: None,
+                    duration_seconds: None,
+                };
+                if self.coerce_to_schema {
+                    coerce_properties(&mut execution.properties, &ty.properties, self.strict)
+                        .with_context(|| format!("execution {}", execution.id))?;
+                }
+                handle_nonfinite(&mut execution.properties, self.nonfinite)
+                    .and_then(|()| {
+                        handle_nonfinite(&mut execution.custom_properties, self.nonfinite)
+                    })
+                    .with_context(|| format!("execution {}", execution.id))?;
+                Ok(execution)
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        if self.failed_inputs {
+            let ids = executions.iter().map(|x| x.id).collect::<Vec<_>>();
+            let mut inputs = self.get_input_artifacts(store, &ids).await?;
+            for execution in &mut executions {
+                execution.inputs = Some(inputs.remove(&execution.id).unwrap_or_default());
+            }
+        }
+        if self.with_duration {
+            let ids = executions.iter().map(|x| x.id).collect::<Vec<_>>();
+            let durations = self.get_durations(store, &ids).await?;
+            for execution in &mut executions {
+                execution.duration_seconds = Some(durations.get(&execution.id).copied());
+            }
+        }
+        Ok(Page {
+            items: executions,
+            truncated,
+        })
+    }
+
+    // Retains the executions that are roots (if `--roots`) and leaves (if `--leaves`) of the lineage graph.
+    async fn retain_roots_and_leaves(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        executions: &mut Vec<mlmd::metadata::Execution>,
+    ) -> anyhow::Result<()> {
+        let execution_ids = executions.iter().map(|x| x.id).collect::<Vec<_>>();
+        let events = get_events(store, &execution_ids).await?;
+        let artifact_ids = events
+            .iter()
+            .map(|x| x.artifact_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut artifact_events = Vec::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let request = store.get_events().artifacts(chunk.iter().copied());
+            artifact_events.extend(request.execute().await?);
+        }
+
+        // Executions that input or output each artifact.
+        let mut related = BTreeMap::<_, Vec<_>>::new();
+        for event in &artifact_events {
+            if let Some(direction) = EventDirection::of(event.ty) {
+                related
+                    .entry((event.artifact_id, direction))
+                    .or_default()
+                    .push(event.execution_id);
+            }
+        }
+
+        // An execution is excluded if one of its inputs (resp. outputs) is output (resp. input) by another execution.
+        let mut excluded = BTreeSet::new();
+        for event in &events {
+            let opposite = match EventDirection::of(event.ty) {
+                Some(EventDirection::Input) if self.roots => EventDirection::Output,
+                Some(EventDirection::Output) if self.leaves => EventDirection::Input,
+                _ => continue,
+            };
+            let others = related.get(&(event.artifact_id, opposite));
+            if others
+                .into_iter()
+                .flatten()
+                .any(|&x| x != event.execution_id)
+            {
+                excluded.insert(event.execution_id);
+            }
+        }
+        executions.retain(|x| !excluded.contains(&x.id));
+        Ok(())
+    }
+
+    // Returns the time from the first input event to the last output event of each execution
+    // (executions lacking either are omitted).
+    async fn get_durations(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        execution_ids: &[i32],
+    ) -> anyhow::Result<BTreeMap<i32, f64>> {
+        let execution_ids = execution_ids
+            .iter()
+            .copied()
+            .map(mlmd::metadata::ExecutionId::new)
+            .collect::<Vec<_>>();
+        let mut starts = BTreeMap::new();
+        let mut ends = BTreeMap::new();
+        for event in get_events(store, &execution_ids).await? {
+            let time = event.create_time_since_epoch;
+            match EventDirection::of(event.ty) {
+                Some(EventDirection::Input) => {
+                    let start = starts.entry(event.execution_id.get()).or_insert(time);
+                    *start = (*start).min(time);
+                }
+                Some(EventDirection::Output) => {
+                    let end = ends.entry(event.execution_id.get()).or_insert(time);
+                    *end = (*end).max(time);
+                }
+                None => {}
+            }
+        }
+        Ok(starts
+            .into_iter()
+            .filter_map(|(id, start)| {
+                let end = ends.get(&id)?;
+                Some((id, end.saturating_sub(start).as_secs_f64()))
             })
             .collect())
     }
 
+    // Returns the input artifacts of each execution.
+    async fn get_input_artifacts(
+        &self,
+        store: &mut mlmd::MetadataStore,
+        execution_ids: &[i32],
+    ) -> anyhow::Result<BTreeMap<i32, Vec<Artifact>>> {
+        let execution_ids = execution_ids
+            .iter()
+            .copied()
+            .map(mlmd::metadata::ExecutionId::new)
+            .collect::<Vec<_>>();
+        let events = get_events(store, &execution_ids).await?;
+        let artifact_ids = events
+            .iter()
+            .filter(|x| EventDirection::of(x.ty) == Some(EventDirection::Input))
+            .map(|x| x.artifact_id)
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let mut artifacts = BTreeMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let request = store.get_artifacts().ids(chunk.iter().copied());
+            artifacts.extend(request.execute().await?.into_iter().map(|x| (x.id, x)));
+        }
+        let artifact_types = store
+            .get_artifact_types()
+            .ids(
+                artifacts
+                    .values()
+                    .map(|x| x.type_id)
+                    .collect::<BTreeSet<_>>()
+                    .into_iter(),
+            )
+            .execute()
+            .await?
+            .into_iter()
+            .map(|x| (x.id, x.name))
+            .collect::<BTreeMap<_, _>>();
+        let artifacts = artifacts
+            .into_iter()
+            .map(|(id, x)| {
+                let ty = artifact_types[&x.type_id].clone();
+                (id, Artifact::new(ty, x, self.time_unit))
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut inputs = BTreeMap::<_, Vec<_>>::new();
+        for event in events {
+            if EventDirection::of(event.ty) != Some(EventDirection::Input) {
+                continue;
+            }
+            if let Some(artifact) = artifacts.get(&event.artifact_id) {
+                inputs
+                    .entry(event.execution_id.get())
+                    .or_default()
+                    .push(artifact.clone());
+            }
+        }
+        for x in inputs.values_mut() {
+            x.sort_by_key(|x| x.id);
+            x.dedup_by_key(|x| x.id);
+        }
+        Ok(inputs)
+    }
+
     async fn get_execution_types(
         &self,
         store: &mut mlmd::MetadataStore,
         executions: &[mlmd::metadata::Execution],
-    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, String>> {
+    ) -> anyhow::Result<BTreeMap<mlmd::metadata::TypeId, Type>> {
         Ok(store
             .get_execution_types()
             .ids(
@@ -263,7 +805,20 @@ impl GetExecutionsOpt {
             .execute()
             .await?
             .into_iter()
-            .map(|x| (x.id, x.name))
+            .map(|x| (x.id, Type::from(x)))
             .collect::<BTreeMap<_, _>>())
     }
 }
+
+// Fetches the events of the executions (in chunks of `ID_CHUNK_SIZE` IDs).
+async fn get_events(
+    store: &mut mlmd::MetadataStore,
+    execution_ids: &[mlmd::metadata::ExecutionId],
+) -> anyhow::Result<Vec<mlmd::metadata::Event>> {
+    let mut events = Vec::new();
+    for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+        let request = store.get_events().executions(chunk.iter().copied());
+        events.extend(request.execute().await?);
+    }
+    Ok(events)
+}
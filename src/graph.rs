@@ -1,19 +1,341 @@
+//! Graph generation.
+use crate::db::ID_CHUNK_SIZE;
+use crate::error::NotFoundError;
+use crate::interrupt::is_interrupted;
+use crate::query::split_key_value;
+use anyhow::Context as _;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, SecondsFormat, TimeZone as _, Utc};
+use indicatif::{ProgressBar, ProgressStyle};
 use mlmd::metadata::{
-    Artifact, ArtifactId, ArtifactType, Event, EventType, Execution, ExecutionId, ExecutionType,
-    TypeId,
+    Artifact, ArtifactId, ArtifactType, ContextId, Event, EventType, Execution, ExecutionId,
+    ExecutionType, PropertyValue, TypeId,
 };
 use mlmd::MetadataStore;
 use palette::{Gradient, Srgb};
-use std::collections::{BTreeMap, HashMap, HashSet};
-use std::io::Write;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::future::Future;
+use std::io::{IsTerminal as _, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::time::{Duration, SystemTime};
 use tinytemplate::TinyTemplate;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum NodeId {
+/// `$ mlmdquery graph *` common options.
+#[derive(Debug, Clone, structopt::StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct CommonGraphOpt {
+    /// Template to generate node URLs.
+    ///
+    /// You can use the following variables in the template:
+    /// - `{node_type}`: "artifact" or "execution":
+    /// - `{id}`: Artifact or Execution ID (depending on `node_type`)
+    ///
+    /// Please refer to the [tinytemplate](https://docs.rs/tinytemplate/) doc for the features of the template engine.
+    #[structopt(long)]
+    pub url_template: Option<String>,
+
+    /// Time zone used to format the timestamps in node tooltips and edge labels.
+    #[structopt(long, default_value = "local", possible_values = TimeZone::POSSIBLE_VALUES, case_insensitive = true)]
+    pub timezone: TimeZone,
+
+    /// If specified, the creation time of each event is appended to the edge label.
+    #[structopt(long)]
+    pub edge_time: bool,
+
+    /// Type names of the nodes to be shown (comma separated).
+    ///
+    /// Nodes of other types are hidden (except the origin nodes), and the shown nodes connected
+    /// through hidden ones are linked by dashed edges.
+    #[structopt(long, use_delimiter = true)]
+    pub only_types: Vec<String>,
+
+    /// Start color of the gradient from which the node colors of types are picked (hex code, e.g., `#ffffff`).
+    #[structopt(long, default_value = "#ffffff")]
+    pub color_start: Srgb<u8>,
+
+    /// End color of the gradient from which the node colors of types are picked (hex code, e.g., `#808080`).
+    #[structopt(long, default_value = "#808080")]
+    pub color_end: Srgb<u8>,
+
+    /// Node shapes per type name (e.g., `Model=cylinder,DataSet=folder`).
+    ///
+    /// The types not listed here are drawn as "ellipse" (artifacts) or "box" (executions).
+    /// Please refer to the [Graphviz](https://graphviz.org/doc/info/shapes.html) doc for the available shapes.
+    #[structopt(long, use_delimiter = true)]
+    pub shape_map: Vec<TypeShape>,
+
+    /// Directory to cache the traversed nodes and edges in.
+    ///
+    /// If a cached result of the same query exists, it is used instead of traversing the DB.
+    #[structopt(long)]
+    pub cache_dir: Option<PathBuf>,
+
+    /// Lifetime of cached results (seconds).
+    #[structopt(long, default_value = "3600")]
+    pub cache_ttl: f64,
+
+    /// Node to which the path from the origin is highlighted (e.g., `3@artifact` or `5@execution`).
+    ///
+    /// The nodes and edges on a shortest path (regardless of the edge directions) are emphasized
+    /// and the rest are dimmed.
+    #[structopt(long, parse(try_from_str = validate_node_id))]
+    pub highlight_path_to: Option<String>,
+
+    /// If specified, the number of visited nodes is shown on stderr during the traversal.
+    #[structopt(long)]
+    pub progress: bool,
+
+    /// If specified, nodes are grouped into clusters by the given criterion.
+    ///
+    /// With `context`, the nodes belonging to the same context are drawn in a cluster labeled with the context name.
+    /// A node belonging to multiple contexts is placed in the cluster of the context having the smallest ID.
+    #[structopt(long, possible_values = ClusterBy::POSSIBLE_VALUES)]
+    pub cluster_by: Option<ClusterBy>,
+
+    /// Maximum number of characters of a node tooltip.
+    ///
+    /// Longer tooltips (e.g., of nodes having large properties) are truncated with "...".
+    #[structopt(long)]
+    pub max_tooltip_len: Option<usize>,
+
+    /// Property names (`properties` or `custom_properties`) whose values are shown in a table beneath each node label (comma separated).
+    ///
+    /// Properties that a node doesn't have are omitted from its table.
+    #[structopt(long, use_delimiter = true)]
+    pub node_properties: Vec<String>,
+
+    /// If specified, each node is labeled with a table of its type name, ID, name, state and the `--node-properties` values.
+    ///
+    /// This is intended for presentation-quality diagrams.
+    #[structopt(long)]
+    pub rich_nodes: bool,
+
+    /// If specified, nodes are named `n1`, `n2`, ... in ascending order of their IDs, and nodes and edges are written in sorted order.
+    ///
+    /// This makes the DOT output of regenerated graphs easier to diff.
+    /// The real IDs are still shown in the node labels and tooltips.
+    #[structopt(long)]
+    pub stable_ids: bool,
+}
+
+impl Default for CommonGraphOpt {
+    fn default() -> Self {
+        // Uses the default values declared in the structopt attributes.
+        <Self as structopt::StructOpt>::from_iter(["mlmdquery"])
+    }
+}
+
+impl CommonGraphOpt {
+    pub(crate) fn load_cache(
+        &self,
+        key: &impl serde::Serialize,
+    ) -> anyhow::Result<Option<Traversal>> {
+        if let Some(dir) = &self.cache_dir {
+            crate::cache::load(dir, Duration::from_secs_f64(self.cache_ttl), key)
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub(crate) fn progress_bar(&self) -> anyhow::Result<ProgressBar> {
+        if !self.progress {
+            return Ok(ProgressBar::hidden());
+        }
+        let progress = ProgressBar::new_spinner();
+        progress.set_style(ProgressStyle::with_template(
+            "{spinner} {pos} nodes visited",
+        )?);
+        progress.enable_steady_tick(Duration::from_millis(100));
+        Ok(progress)
+    }
+
+    pub(crate) fn save_cache(
+        &self,
+        key: &impl serde::Serialize,
+        traversal: &Traversal,
+    ) -> anyhow::Result<()> {
+        if let Some(dir) = &self.cache_dir {
+            crate::cache::save(dir, key, traversal)?;
+        }
+        Ok(())
+    }
+
+    /// Traverses the graph breadth-first from `origins`, following the edges returned by `get_edges`.
+    ///
+    /// Nodes `radius` edges away from the origins are recorded as boundaries and not expanded.
+    /// The traversal is truncated once `max_nodes` nodes have been visited or Ctrl-C is pressed.
+    pub(crate) async fn traverse<F>(
+        &self,
+        store: &mut MetadataStore,
+        origins: Vec<NodeId>,
+        max_nodes: Option<usize>,
+        radius: Option<usize>,
+        mut get_edges: F,
+    ) -> anyhow::Result<Traversal>
+    where
+        F: for<'a> FnMut(&'a mut MetadataStore, NodeId) -> EdgesFuture<'a>,
+    {
+        let mut queue = origins
+            .into_iter()
+            .map(|id| (id, 0))
+            .collect::<VecDeque<_>>();
+        let mut traversal = Traversal::default();
+        let progress = self.progress_bar()?;
+        while let Some((id, distance)) = queue.pop_front() {
+            if traversal.nodes.contains_key(&id) {
+                continue;
+            }
+            if matches!(max_nodes, Some(n) if traversal.nodes.len() >= n) || is_interrupted() {
+                traversal.truncated = true;
+                break;
+            }
+
+            let node = get_node(store, id).await?;
+            traversal.nodes.insert(id, node);
+            progress.inc(1);
+
+            if radius == Some(distance) {
+                traversal.boundaries.insert(id);
+                continue;
+            }
+
+            for edge in get_edges(store, id).await? {
+                queue.push_back((edge.from_node(), distance + 1));
+                queue.push_back((edge.to_node(), distance + 1));
+                traversal.edges.insert(edge);
+            }
+        }
+        progress.finish_and_clear();
+        Ok(traversal)
+    }
+}
+
+/// Node shape associated with a type name (`<TYPE>=<SHAPE>`).
+///
+/// `=` in a type name can be escaped as `\=` (and `\` as `\\`).
+#[derive(Debug, Clone)]
+pub struct TypeShape {
+    /// Type name.
+    pub type_name: String,
+
+    /// Graphviz node shape.
+    pub shape: String,
+}
+
+impl std::str::FromStr for TypeShape {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match split_key_value(s) {
+            Some((type_name, shape)) if !type_name.is_empty() && !shape.is_empty() => Ok(Self {
+                type_name,
+                shape: shape.to_owned(),
+            }),
+            _ => anyhow::bail!("invalid value: {:?} (expected `<TYPE>=<SHAPE>`)", s),
+        }
+    }
+}
+
+/// Time zone used to format (or parse) timestamps.
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+pub enum TimeZone {
+    Local,
+    Utc,
+}
+
+impl TimeZone {
+    pub(crate) const POSSIBLE_VALUES: &'static [&'static str] = &["local", "utc"];
+
+    pub(crate) fn utc() -> Self {
+        Self::Utc
+    }
+
+    // Parses a date string (RFC 3339, `YYYY-MM-DD[( |T)hh:mm:ss[.fff]]`) into the time since the UNIX epoch.
+    // Strings without an offset are interpreted in this time zone.
+    pub(crate) fn parse(self, s: &str) -> anyhow::Result<Duration> {
+        let time = if let Ok(x) = DateTime::parse_from_rfc3339(s) {
+            x.with_timezone(&Utc)
+        } else {
+            let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"]
+                .iter()
+                .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+                .or_else(|| {
+                    NaiveDate::parse_from_str(s, "%Y-%m-%d")
+                        .ok()
+                        .and_then(|x| x.and_hms_opt(0, 0, 0))
+                })
+                .ok_or_else(|| anyhow::anyhow!("invalid date: {:?}", s))?;
+            match self {
+                Self::Local => Local
+                    .from_local_datetime(&naive)
+                    .earliest()
+                    .ok_or_else(|| anyhow::anyhow!("nonexistent local time: {:?}", s))?
+                    .with_timezone(&Utc),
+                Self::Utc => Utc.from_utc_datetime(&naive),
+            }
+        };
+        let millis = u64::try_from(time.timestamp_millis())
+            .map_err(|_| anyhow::anyhow!("date before the UNIX epoch: {:?}", s))?;
+        Ok(Duration::from_millis(millis))
+    }
+
+    pub(crate) fn format(self, time_since_epoch: Duration) -> String {
+        let time = SystemTime::UNIX_EPOCH + time_since_epoch;
+        match self {
+            Self::Local => {
+                DateTime::<Local>::from(time).to_rfc3339_opts(SecondsFormat::AutoSi, true)
+            }
+            Self::Utc => DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        }
+    }
+}
+
+impl std::str::FromStr for TimeZone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "local" => Ok(Self::Local),
+            "utc" => Ok(Self::Utc),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub(crate) enum NodeId {
     Artifact(ArtifactId),
     Execution(ExecutionId),
 }
 
+impl std::str::FromStr for NodeId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let invalid = || {
+            anyhow::anyhow!(
+                "invalid node: {:?} (expected `<ID>@artifact` or `<ID>@execution`)",
+                s
+            )
+        };
+        let (id, kind) = s.split_once('@').ok_or_else(invalid)?;
+        let id = id.parse().map_err(|_| invalid())?;
+        match kind {
+            "artifact" => Ok(Self::Artifact(ArtifactId::new(id))),
+            "execution" => Ok(Self::Execution(ExecutionId::new(id))),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+fn validate_node_id(s: &str) -> anyhow::Result<String> {
+    s.parse::<NodeId>()?;
+    Ok(s.to_owned())
+}
+
 impl std::fmt::Display for NodeId {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -24,36 +346,101 @@ impl std::fmt::Display for NodeId {
 }
 
 #[derive(Debug)]
-pub enum Node {
+pub(crate) enum Node {
     Artifact(Artifact),
     Execution(Execution),
 }
 
 impl Node {
-    pub fn id(&self) -> NodeId {
+    pub(crate) fn id(&self) -> NodeId {
         match self {
             Self::Artifact(x) => NodeId::Artifact(x.id),
             Self::Execution(x) => NodeId::Execution(x.id),
         }
     }
 
-    pub fn label(&self) -> String {
+    pub(crate) fn label(&self) -> String {
         match self {
             Self::Artifact(x) => x.id.get().to_string(),
             Self::Execution(x) => x.id.get().to_string(),
         }
     }
 
-    pub fn color(&self, colors: &HashMap<TypeId, Srgb<u8>>) -> String {
-        let type_id = match self {
+    // Makes a Graphviz HTML-like label consisting of the node ID and a table of the given properties.
+    //
+    // Returns `None` if the node has none of the properties.
+    pub(crate) fn html_label(&self, property_names: &[String]) -> Option<String> {
+        let rows = self.property_rows(property_names);
+        if rows.is_empty() {
+            return None;
+        }
+        Some(html_table(&escape_html(&self.label()), &rows))
+    }
+
+    // Makes a Graphviz HTML-like label (for `--rich-nodes`) consisting of the type name,
+    // ID, name, state and the given properties of the node.
+    pub(crate) fn rich_label(
+        &self,
+        types: &BTreeMap<TypeId, Type>,
+        property_names: &[String],
+    ) -> String {
+        let (name, state) = match self {
+            Self::Artifact(x) => (
+                x.name.clone(),
+                crate::serialize::state_name(crate::serialize::ArtifactState::from(x.state)),
+            ),
+            Self::Execution(x) => (
+                x.name.clone(),
+                crate::serialize::state_name(crate::serialize::ExecutionState::from(
+                    x.last_known_state,
+                )),
+            ),
+        };
+        let mut rows = vec![("id".to_owned(), self.label())];
+        if let Some(name) = name {
+            rows.push(("name".to_owned(), name));
+        }
+        rows.push(("state".to_owned(), state));
+        rows.extend(self.property_rows(property_names));
+        let header = format!("<B>{}</B>", escape_html(types[&self.type_id()].name()));
+        html_table(&header, &rows)
+    }
+
+    // Returns the names and values of the given properties that the node has.
+    fn property_rows(&self, property_names: &[String]) -> Vec<(String, String)> {
+        let (properties, custom_properties) = match self {
+            Self::Artifact(x) => (&x.properties, &x.custom_properties),
+            Self::Execution(x) => (&x.properties, &x.custom_properties),
+        };
+        property_names
+            .iter()
+            .filter_map(|name| {
+                let value = properties
+                    .get(name)
+                    .or_else(|| custom_properties.get(name))?;
+                let value = match value {
+                    PropertyValue::Int(v) => v.to_string(),
+                    PropertyValue::Double(v) => v.to_string(),
+                    PropertyValue::String(v) => v.clone(),
+                };
+                Some((name.clone(), value))
+            })
+            .collect()
+    }
+
+    pub(crate) fn type_id(&self) -> TypeId {
+        match self {
             Self::Artifact(x) => x.type_id,
             Self::Execution(x) => x.type_id,
-        };
-        let color = colors[&type_id];
+        }
+    }
+
+    pub(crate) fn color(&self, colors: &HashMap<TypeId, Srgb<u8>>) -> String {
+        let color = colors[&self.type_id()];
         format!("#{:02x}{:02x}{:02x}", color.red, color.green, color.blue)
     }
 
-    pub fn url(&self, template: Option<&TinyTemplate>) -> anyhow::Result<String> {
+    pub(crate) fn url(&self, template: Option<&TinyTemplate>) -> anyhow::Result<String> {
         if let Some(tt) = template {
             let context = match self {
                 Self::Artifact(x) => UrlTemplateContext {
@@ -71,27 +458,35 @@ impl Node {
         }
     }
 
-    pub fn shape(&self) -> &str {
-        match self {
-            Self::Artifact(_) => "ellipse",
-            Self::Execution(_) => "box",
-        }
+    pub(crate) fn shape<'a>(
+        &self,
+        types: &'a BTreeMap<TypeId, Type>,
+        shape_map: &'a [TypeShape],
+    ) -> &'a str {
+        types[&self.type_id()].shape(shape_map)
     }
 
-    pub fn style(&self, origin: NodeId) -> &str {
-        if self.id() == origin {
+    pub(crate) fn style(&self, origins: &[NodeId], boundaries: &HashSet<NodeId>) -> &str {
+        if origins.contains(&self.id()) {
             "bold,dashed,filled"
+        } else if boundaries.contains(&self.id()) {
+            "dotted,filled"
         } else {
             "solid,filled"
         }
     }
 
-    pub fn tooltip(&self, types: &BTreeMap<TypeId, Type>) -> anyhow::Result<String> {
+    pub(crate) fn tooltip(
+        &self,
+        types: &BTreeMap<TypeId, Type>,
+        timezone: TimeZone,
+    ) -> anyhow::Result<String> {
         match self {
             Self::Artifact(x) => {
                 let artifact = crate::serialize::ArtifactNode::new(
                     types[&x.type_id].name().to_owned(),
                     x.clone(),
+                    timezone,
                 );
                 Ok(serde_json::to_string_pretty(&artifact)?)
             }
@@ -99,6 +494,7 @@ impl Node {
                 let execution = crate::serialize::ExecutionNode::new(
                     types[&x.type_id].name().to_owned(),
                     x.clone(),
+                    timezone,
                 );
                 Ok(serde_json::to_string_pretty(&execution)?)
             }
@@ -107,16 +503,20 @@ impl Node {
 }
 
 #[derive(Debug, PartialEq, Eq, Hash)]
-pub struct Edge {
+pub(crate) struct Edge {
     event: Event,
 }
 
 impl Edge {
-    pub fn new(event: Event) -> Self {
+    pub(crate) fn new(event: Event) -> Self {
         Self { event }
     }
 
-    pub fn label(&self) -> anyhow::Result<String> {
+    pub(crate) fn event(&self) -> &Event {
+        &self.event
+    }
+
+    pub(crate) fn label(&self) -> anyhow::Result<String> {
         let path = self
             .event
             .path
@@ -130,8 +530,12 @@ impl Edge {
         Ok(serde_json::to_string(&path)?)
     }
 
+    pub(crate) fn time(&self, timezone: TimeZone) -> String {
+        timezone.format(self.event.create_time_since_epoch)
+    }
+
     #[allow(clippy::wrong_self_convention)]
-    pub fn from_node(&self) -> NodeId {
+    pub(crate) fn from_node(&self) -> NodeId {
         let is_input = matches!(
             self.event.ty,
             EventType::Input | EventType::DeclaredInput | EventType::InternalInput
@@ -143,7 +547,7 @@ impl Edge {
         }
     }
 
-    pub fn to_node(&self) -> NodeId {
+    pub(crate) fn to_node(&self) -> NodeId {
         let is_input = matches!(
             self.event.ty,
             EventType::Input | EventType::DeclaredInput | EventType::InternalInput
@@ -156,6 +560,53 @@ impl Edge {
     }
 }
 
+// Makes a Graphviz HTML-like table having a header (already escaped) and rows of names and values.
+fn html_table(header: &str, rows: &[(String, String)]) -> String {
+    let rows = rows
+        .iter()
+        .map(|(name, value)| {
+            format!(
+                "<TR><TD ALIGN=\"LEFT\">{}</TD><TD ALIGN=\"LEFT\">{}</TD></TR>",
+                escape_html(name),
+                escape_html(value)
+            )
+        })
+        .collect::<String>();
+    format!(
+        "<TABLE BORDER=\"0\" CELLBORDER=\"1\" CELLSPACING=\"0\"><TR><TD COLSPAN=\"2\" BORDER=\"0\">{}</TD></TR>{}</TABLE>",
+        header, rows
+    )
+}
+
+// Escapes the characters having special meanings in Graphviz HTML-like labels.
+fn escape_html(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            '\n' => escaped.push_str("<BR/>"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Truncates `tooltip` to `max_len` characters (excluding the trailing "...").
+fn truncate_tooltip(tooltip: String, max_len: Option<usize>) -> String {
+    match max_len {
+        Some(n) if tooltip.chars().count() > n => {
+            let mut truncated = tooltip.chars().take(n).collect::<String>();
+            truncated.push_str("...");
+            truncated
+        }
+        _ => tooltip,
+    }
+}
+
 #[derive(Debug, serde::Serialize)]
 struct UrlTemplateContext {
     node_type: &'static str,
@@ -163,27 +614,30 @@ struct UrlTemplateContext {
 }
 
 #[derive(Debug)]
-pub enum Type {
+pub(crate) enum Type {
     Artifact(ArtifactType),
     Execution(ExecutionType),
 }
 
 impl Type {
-    pub fn id(&self) -> TypeId {
+    pub(crate) fn id(&self) -> TypeId {
         match self {
             Self::Artifact(x) => x.id,
             Self::Execution(x) => x.id,
         }
     }
 
-    pub fn name(&self) -> &str {
+    pub(crate) fn name(&self) -> &str {
         match self {
             Self::Artifact(x) => &x.name,
             Self::Execution(x) => &x.name,
         }
     }
 
-    pub fn shape(&self) -> &str {
+    pub(crate) fn shape<'a>(&'a self, shape_map: &'a [TypeShape]) -> &'a str {
+        if let Some(x) = shape_map.iter().find(|x| x.type_name == self.name()) {
+            return &x.shape;
+        }
         match self {
             Self::Artifact(_) => "ellipse",
             Self::Execution(_) => "box",
@@ -191,23 +645,143 @@ impl Type {
     }
 }
 
+/// Origin ID given as a positional argument (`-` means that the IDs are read from stdin).
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub enum OriginId {
+    Id(i32),
+    Stdin,
+}
+
+impl std::str::FromStr for OriginId {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if s == "-" {
+            Ok(Self::Stdin)
+        } else {
+            Ok(Self::Id(s.parse().with_context(|| {
+                format!("invalid ID: {:?} (expected an integer or `-`)", s)
+            })?))
+        }
+    }
+}
+
+// Returns the IDs in `args`, reading IDs (separated by whitespace) from stdin
+// if `-` is included or no ID is given.
+pub(crate) fn resolve_origin_ids(args: &[OriginId]) -> anyhow::Result<Vec<i32>> {
+    if args.is_empty() && std::io::stdin().is_terminal() {
+        anyhow::bail!("no ID is given (please specify IDs as arguments or via stdin)");
+    }
+
+    let mut ids = args
+        .iter()
+        .filter_map(|x| match x {
+            OriginId::Id(id) => Some(*id),
+            OriginId::Stdin => None,
+        })
+        .collect::<Vec<_>>();
+    if args.is_empty() || args.iter().any(|x| matches!(x, OriginId::Stdin)) {
+        let text = std::io::read_to_string(std::io::stdin())?;
+        for x in text.split_whitespace() {
+            ids.push(
+                x.parse()
+                    .with_context(|| format!("invalid ID in stdin: {:?}", x))?,
+            );
+        }
+    }
+    anyhow::ensure!(!ids.is_empty(), "no ID is given");
+    Ok(ids)
+}
+
+/// Criterion to group graph nodes into clusters.
+#[derive(Debug, Clone, Copy)]
+#[allow(missing_docs)]
+pub enum ClusterBy {
+    Context,
+}
+
+impl ClusterBy {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["context"];
+}
+
+impl std::str::FromStr for ClusterBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "context" => Ok(Self::Context),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+/// Nodes and edges collected by a graph traversal.
+#[derive(Debug, Default)]
+pub(crate) struct Traversal {
+    pub nodes: HashMap<NodeId, Node>,
+    pub edges: HashSet<Edge>,
+    pub boundaries: HashSet<NodeId>,
+    pub truncated: bool,
+}
+
+/// Future of the edges of a node to be followed by [`CommonGraphOpt::traverse`].
+pub(crate) type EdgesFuture<'a> =
+    Pin<Box<dyn Future<Output = anyhow::Result<Vec<Edge>>> + Send + 'a>>;
+
+async fn get_node(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Node> {
+    match id {
+        NodeId::Artifact(id) => {
+            let mut artifacts = store.get_artifacts().id(id).execute().await?;
+            anyhow::ensure!(
+                artifacts.len() == 1,
+                NotFoundError::new(format!("No such artifact: {}", id.get()))
+            );
+            Ok(Node::Artifact(artifacts.remove(0)))
+        }
+        NodeId::Execution(id) => {
+            let mut executions = store.get_executions().id(id).execute().await?;
+            anyhow::ensure!(
+                executions.len() == 1,
+                NotFoundError::new(format!("No such execution: {}", id.get()))
+            );
+            Ok(Node::Execution(executions.remove(0)))
+        }
+    }
+}
+
 #[derive(Debug)]
-pub struct Graph {
-    origin: NodeId,
+struct GraphPath {
+    nodes: HashSet<NodeId>,
+    // Contains both directions of each edge.
+    edges: HashSet<(NodeId, NodeId)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Graph {
+    origins: Vec<NodeId>,
+    boundaries: HashSet<NodeId>,
     nodes: HashMap<NodeId, Node>,
     edges: HashSet<Edge>,
     types: BTreeMap<TypeId, Type>,
     colors: HashMap<TypeId, Srgb<u8>>,
-    url_template: Option<String>,
+    clusters: BTreeMap<ContextId, Cluster>,
+    options: CommonGraphOpt,
+}
+
+#[derive(Debug)]
+struct Cluster {
+    label: String,
+    nodes: Vec<NodeId>,
 }
 
 impl Graph {
     pub async fn new(
         store: &mut MetadataStore,
-        origin: NodeId,
+        origins: Vec<NodeId>,
         nodes: HashMap<NodeId, Node>,
         edges: HashSet<Edge>,
-        url_template: Option<String>,
+        options: CommonGraphOpt,
     ) -> anyhow::Result<Self> {
         let mut types = BTreeMap::new();
         types.extend(
@@ -255,8 +829,8 @@ impl Graph {
         let execution_type_count = types.len() - artifact_type_count;
 
         let gradient = Gradient::new(vec![
-            Srgb::new(1.0, 1.0, 1.0).into_linear(),
-            Srgb::new(0.5, 0.5, 0.5).into_linear(),
+            options.color_start.into_format::<f32>().into_linear(),
+            options.color_end.into_format::<f32>().into_linear(),
         ]);
         let colors = types
             .iter()
@@ -284,18 +858,223 @@ impl Graph {
             )
             .collect();
 
+        let clusters = match options.cluster_by {
+            None => BTreeMap::new(),
+            Some(ClusterBy::Context) => Self::cluster_by_context(store, &nodes).await?,
+        };
+
         Ok(Self {
-            origin,
+            origins,
+            boundaries: HashSet::new(),
             nodes,
             edges,
             types,
             colors,
-            url_template,
+            clusters,
+            options,
         })
     }
 
-    pub fn generate<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
-        let url_template = if let Some(x) = &self.url_template {
+    // Places each node in the cluster of the context having the smallest ID among those it belongs to.
+    async fn cluster_by_context(
+        store: &mut MetadataStore,
+        nodes: &HashMap<NodeId, Node>,
+    ) -> anyhow::Result<BTreeMap<ContextId, Cluster>> {
+        let mut artifact_ids = Vec::new();
+        let mut execution_ids = Vec::new();
+        for id in nodes.keys() {
+            match *id {
+                NodeId::Artifact(id) => artifact_ids.push(id),
+                NodeId::Execution(id) => execution_ids.push(id),
+            }
+        }
+
+        let mut contexts = BTreeMap::new();
+        for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+            let request = store.get_contexts().artifacts(chunk.iter().copied());
+            contexts.extend(request.execute().await?.into_iter().map(|x| (x.id, x)));
+        }
+        for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+            let request = store.get_contexts().executions(chunk.iter().copied());
+            contexts.extend(request.execute().await?.into_iter().map(|x| (x.id, x)));
+        }
+
+        let mut assigned = HashSet::new();
+        let mut clusters = BTreeMap::new();
+        for (id, context) in contexts {
+            // Only the members that are nodes of the graph are fetched
+            // (a context can have far more members than the graph has nodes).
+            let mut members = Vec::new();
+            for chunk in artifact_ids.chunks(ID_CHUNK_SIZE) {
+                let request = store.get_artifacts().context(id).ids(chunk.iter().copied());
+                members.extend(
+                    request
+                        .execute()
+                        .await?
+                        .into_iter()
+                        .map(|x| NodeId::Artifact(x.id)),
+                );
+            }
+            for chunk in execution_ids.chunks(ID_CHUNK_SIZE) {
+                let request = store
+                    .get_executions()
+                    .context(id)
+                    .ids(chunk.iter().copied());
+                members.extend(
+                    request
+                        .execute()
+                        .await?
+                        .into_iter()
+                        .map(|x| NodeId::Execution(x.id)),
+                );
+            }
+            members.sort();
+            let nodes = members
+                .into_iter()
+                .filter(|x| assigned.insert(*x))
+                .collect::<Vec<_>>();
+            if !nodes.is_empty() {
+                clusters.insert(
+                    id,
+                    Cluster {
+                        label: context.name,
+                        nodes,
+                    },
+                );
+            }
+        }
+        Ok(clusters)
+    }
+
+    pub(crate) fn set_boundaries(&mut self, boundaries: HashSet<NodeId>) {
+        self.boundaries = boundaries;
+    }
+
+    // Removes the edges that refer to unfetched nodes and marks their fetched ends as boundaries.
+    pub(crate) fn prune_dangling_edges(&mut self) {
+        let nodes = &self.nodes;
+        let boundaries = &mut self.boundaries;
+        self.edges.retain(|edge| {
+            let from = edge.from_node();
+            let to = edge.to_node();
+            match (nodes.contains_key(&from), nodes.contains_key(&to)) {
+                (true, true) => true,
+                (true, false) => {
+                    boundaries.insert(from);
+                    false
+                }
+                (false, true) => {
+                    boundaries.insert(to);
+                    false
+                }
+                (false, false) => false,
+            }
+        });
+    }
+
+    // Returns the nodes hidden by `--only-types` and the edges connecting
+    // the shown nodes through the hidden ones.
+    fn contract(&self) -> (HashSet<NodeId>, HashSet<(NodeId, NodeId)>) {
+        let mut contracted_edges = HashSet::new();
+        if self.options.only_types.is_empty() {
+            return (HashSet::new(), contracted_edges);
+        }
+
+        let hidden = self
+            .nodes
+            .values()
+            .filter(|node| {
+                let type_name = self.types[&node.type_id()].name();
+                !self.origins.contains(&node.id())
+                    && !self.options.only_types.iter().any(|x| x == type_name)
+            })
+            .map(|node| node.id())
+            .collect::<HashSet<_>>();
+
+        let mut successors = HashMap::<_, Vec<_>>::new();
+        for edge in &self.edges {
+            successors
+                .entry(edge.from_node())
+                .or_default()
+                .push(edge.to_node());
+        }
+        for &from in self.nodes.keys().filter(|x| !hidden.contains(x)) {
+            let mut stack = successors
+                .get(&from)
+                .into_iter()
+                .flatten()
+                .copied()
+                .filter(|x| hidden.contains(x))
+                .collect::<Vec<_>>();
+            let mut visited = HashSet::new();
+            while let Some(id) = stack.pop() {
+                if !visited.insert(id) {
+                    continue;
+                }
+                for &to in successors.get(&id).into_iter().flatten() {
+                    if hidden.contains(&to) {
+                        stack.push(to);
+                    } else if self.nodes.contains_key(&to) {
+                        contracted_edges.insert((from, to));
+                    }
+                }
+            }
+        }
+        (hidden, contracted_edges)
+    }
+
+    // Returns the nodes and the edges on a shortest path from an origin to `target`
+    // (edge directions are ignored), or `None` if there is no such path.
+    fn find_path(&self, target: NodeId) -> Option<GraphPath> {
+        if !self.nodes.contains_key(&target) {
+            return None;
+        }
+
+        let mut neighbors = HashMap::<_, Vec<_>>::new();
+        for edge in &self.edges {
+            neighbors
+                .entry(edge.from_node())
+                .or_default()
+                .push(edge.to_node());
+            neighbors
+                .entry(edge.to_node())
+                .or_default()
+                .push(edge.from_node());
+        }
+
+        let mut prev = HashMap::new();
+        let mut queue = VecDeque::new();
+        for &origin in &self.origins {
+            prev.insert(origin, None);
+            queue.push_back(origin);
+        }
+        while let Some(id) = queue.pop_front() {
+            if id == target {
+                break;
+            }
+            for &next in neighbors.get(&id).into_iter().flatten() {
+                if let Entry::Vacant(entry) = prev.entry(next) {
+                    entry.insert(Some(id));
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        let mut nodes = HashSet::new();
+        let mut edges = HashSet::new();
+        let mut current = target;
+        nodes.insert(current);
+        while let Some(next) = *prev.get(&current)? {
+            nodes.insert(next);
+            edges.insert((current, next));
+            edges.insert((next, current));
+            current = next;
+        }
+        Some(GraphPath { nodes, edges })
+    }
+
+    pub(crate) fn generate<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        let url_template = if let Some(x) = &self.options.url_template {
             let mut tt = TinyTemplate::new();
             tt.add_template("url", x)?;
             Some(tt)
@@ -303,49 +1082,181 @@ impl Graph {
             None
         };
 
+        let (hidden, contracted_edges) = self.contract();
+
+        let path = if let Some(target) = &self.options.highlight_path_to {
+            let target = target.parse()?;
+            let path = self.find_path(target);
+            if path.is_none() {
+                eprintln!("warning: no path to {} was found in the graph", target);
+            }
+            path
+        } else {
+            None
+        };
+        const HIGHLIGHTED: &str = ",color=\"red\",penwidth=3";
+        const DIMMED: &str = ",color=\"#c0c0c0\",fontcolor=\"#a0a0a0\"";
+        let node_highlight = |id: NodeId| match &path {
+            None => "",
+            Some(path) if path.nodes.contains(&id) => HIGHLIGHTED,
+            Some(_) => DIMMED,
+        };
+        let edge_highlight = |from: NodeId, to: NodeId| match &path {
+            None => "",
+            Some(path) if path.edges.contains(&(from, to)) => HIGHLIGHTED,
+            Some(_) => DIMMED,
+        };
+        // The path may go through the nodes hidden by `--only-types`.
+        let contracted_highlight = |from: NodeId, to: NodeId| match &path {
+            None => "",
+            Some(path) if path.nodes.contains(&from) && path.nodes.contains(&to) => HIGHLIGHTED,
+            Some(_) => DIMMED,
+        };
+
+        let mut sorted_ids = self.nodes.keys().copied().collect::<Vec<_>>();
+        sorted_ids.sort();
+        let stable_names = if self.options.stable_ids {
+            sorted_ids
+                .iter()
+                .enumerate()
+                .map(|(i, id)| (*id, format!("n{}", i + 1)))
+                .collect::<HashMap<_, _>>()
+        } else {
+            HashMap::new()
+        };
+        let node_name = |id: NodeId| {
+            stable_names
+                .get(&id)
+                .cloned()
+                .unwrap_or_else(|| id.to_string())
+        };
+
         writeln!(writer, "digraph artifact_lineage_graph {{")?;
         writeln!(writer, "  concentrate=true;")?;
-        for node in self.nodes.values() {
+        let write_node = |writer: &mut W, indent: &str, node: &Node| -> anyhow::Result<()> {
+            let html = if self.options.rich_nodes {
+                Some(node.rich_label(&self.types, &self.options.node_properties))
+            } else {
+                node.html_label(&self.options.node_properties)
+            };
+            let label = match html {
+                Some(html) => format!("<{}>", html),
+                None => format!("{:?}", node.label()),
+            };
             writeln!(
                 writer,
-                "  {:?} [label={:?},shape={:?},style={:?},tooltip={:?},fillcolor={:?},URL={:?}];",
-                node.id().to_string(),
-                node.label(),
-                node.shape(),
-                node.style(self.origin),
-                node.tooltip(&self.types)?,
+                "{}{:?} [label={},shape={:?},style={:?},tooltip={:?},fillcolor={:?},URL={:?}{}];",
+                indent,
+                node_name(node.id()),
+                label,
+                node.shape(&self.types, &self.options.shape_map),
+                node.style(&self.origins, &self.boundaries),
+                truncate_tooltip(
+                    node.tooltip(&self.types, self.options.timezone)?,
+                    self.options.max_tooltip_len
+                ),
                 node.color(&self.colors),
-                node.url(url_template.as_ref())?
+                node.url(url_template.as_ref())?,
+                node_highlight(node.id())
             )?;
+            Ok(())
+        };
+        let mut clustered = HashSet::new();
+        for (id, cluster) in &self.clusters {
+            writeln!(writer, "  subgraph cluster_context_{} {{", id.get())?;
+            writeln!(writer, "    label = {:?};", cluster.label)?;
+            let mut nodes = cluster.nodes.clone();
+            if self.options.stable_ids {
+                nodes.sort();
+            }
+            for node in &nodes {
+                clustered.insert(*node);
+                if !hidden.contains(node) {
+                    write_node(writer, "    ", &self.nodes[node])?;
+                }
+            }
+            writeln!(writer, "  }}")?;
+        }
+        let nodes = if self.options.stable_ids {
+            sorted_ids
+                .iter()
+                .map(|id| &self.nodes[id])
+                .collect::<Vec<_>>()
+        } else {
+            self.nodes.values().collect()
+        };
+        for node in nodes {
+            if hidden.contains(&node.id()) || clustered.contains(&node.id()) {
+                continue;
+            }
+            write_node(writer, "  ", node)?;
         }
 
+        let mut edge_lines = Vec::new();
         let mut visited_edges = HashSet::new();
         for edge in &self.edges {
-            let key = (edge.from_node(), edge.to_node(), edge.label()?);
+            if hidden.contains(&edge.from_node()) || hidden.contains(&edge.to_node()) {
+                continue;
+            }
+
+            let mut label = edge.label()?;
+            if self.options.edge_time {
+                if !label.is_empty() {
+                    label.push(' ');
+                }
+                label.push_str(&edge.time(self.options.timezone));
+            }
+
+            let key = (edge.from_node(), edge.to_node(), label.clone());
             if visited_edges.contains(&key) {
                 continue;
             }
             visited_edges.insert(key);
 
-            writeln!(
-                writer,
-                "  {:?} -> {:?} [label={:?}];",
-                self.nodes[&edge.from_node()].id().to_string(),
-                self.nodes[&edge.to_node()].id().to_string(),
-                edge.label()?
-            )?;
+            edge_lines.push(format!(
+                "  {:?} -> {:?} [label={:?}{}];",
+                node_name(self.nodes[&edge.from_node()].id()),
+                node_name(self.nodes[&edge.to_node()].id()),
+                label,
+                edge_highlight(edge.from_node(), edge.to_node())
+            ));
+        }
+        for (from, to) in &contracted_edges {
+            edge_lines.push(format!(
+                "  {:?} -> {:?} [style=dashed{}];",
+                node_name(*from),
+                node_name(*to),
+                contracted_highlight(*from, *to)
+            ));
+        }
+        if self.options.stable_ids {
+            edge_lines.sort();
+        }
+        for line in edge_lines {
+            writeln!(writer, "{}", line)?;
+        }
+
+        let mut counts = HashMap::<_, usize>::new();
+        for node in self.nodes.values() {
+            if !hidden.contains(&node.id()) {
+                *counts.entry(node.type_id()).or_default() += 1;
+            }
         }
 
         writeln!(writer, "  subgraph cluster_artifact_legend {{")?;
         writeln!(writer, "    label = \"Artifact Legend\";")?;
         let mut prev = None;
         for ty in self.types.values() {
+            if !self.options.only_types.is_empty() && !counts.contains_key(&ty.id()) {
+                continue;
+            }
             if matches!(ty, Type::Artifact(_)) {
                 writeln!(
                     writer,
-                    "    {:?}[shape={:?},style=filled,fillcolor=\"#{:02x}{:02x}{:02x}\"];",
+                    "    {:?}[label={:?},shape={:?},style=filled,fillcolor=\"#{:02x}{:02x}{:02x}\"];",
                     ty.name(),
-                    ty.shape(),
+                    format!("{} ({})", ty.name(), counts.get(&ty.id()).copied().unwrap_or(0)),
+                    ty.shape(&self.options.shape_map),
                     self.colors[&ty.id()].red,
                     self.colors[&ty.id()].green,
                     self.colors[&ty.id()].blue
@@ -367,12 +1278,16 @@ impl Graph {
         writeln!(writer, "    label = \"Execution Legend\";")?;
         let mut prev = None;
         for ty in self.types.values() {
+            if !self.options.only_types.is_empty() && !counts.contains_key(&ty.id()) {
+                continue;
+            }
             if matches!(ty, Type::Execution(_)) {
                 writeln!(
                     writer,
-                    "    {:?}[shape={:?},style=filled,fillcolor=\"#{:02x}{:02x}{:02x}\"];",
+                    "    {:?}[label={:?},shape={:?},style=filled,fillcolor=\"#{:02x}{:02x}{:02x}\"];",
                     ty.name(),
-                    ty.shape(),
+                    format!("{} ({})", ty.name(), counts.get(&ty.id()).copied().unwrap_or(0)),
+                    ty.shape(&self.options.shape_map),
                     self.colors[&ty.id()].red,
                     self.colors[&ty.id()].green,
                     self.colors[&ty.id()].blue
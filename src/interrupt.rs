@@ -0,0 +1,29 @@
+//! Handling of Ctrl-C (SIGINT).
+//!
+//! On the first Ctrl-C, paged fetches and graph traversals stop, the result collected so far is output,
+//! and the process exits with status 130.
+//! The second Ctrl-C terminates the process immediately.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the Ctrl-C handler (must be called within a tokio runtime).
+pub fn install_handler() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        INTERRUPTED.store(true, Ordering::SeqCst);
+        eprintln!(
+            "interrupted: outputting the partial result (press Ctrl-C again to quit immediately)"
+        );
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+}
+
+/// Returns whether Ctrl-C has been pressed.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
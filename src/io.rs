@@ -1,77 +1,117 @@
 //! `$ mlmdquery graph io` implementation.
-use crate::graph::{Edge, Graph, Node, NodeId};
+use crate::db::DbOpt;
+use crate::graph::{resolve_origin_ids, CommonGraphOpt, Edge, Graph, NodeId, OriginId};
+use crate::interrupt::is_interrupted;
 use mlmd::metadata::ExecutionId;
 use mlmd::MetadataStore;
-use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
 /// `$ mlmdquery graph io` options.
 #[derive(Debug, structopt::StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct GraphIoOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
 
-    /// Target execution ID.
-    pub execution: i32,
-
-    /// Template to generate node URLs.
-    ///
-    /// You can use the following variables in the template:
-    /// - `{node_type}`: "artifact" or "execution":
-    /// - `{id}`: Artifact or Execution ID (depending on `node_type`)
+    /// Target execution IDs.
     ///
-    /// Please refer to the [tinytemplate](https://docs.rs/tinytemplate/) doc for the features of the template engine.
+    /// If `-` is specified or no ID is given, IDs separated by whitespace are read from stdin.
+    /// A graph is generated per ID unless `--combine` is specified.
+    pub executions: Vec<OriginId>,
+
+    /// If specified, all the target executions are drawn in a single graph.
     #[structopt(long)]
-    pub url_template: Option<String>,
+    pub combine: bool,
+
+    /// Maximum number of nodes in a graph.
+    ///
+    /// If the traversal reaches this limit, the graph is truncated and a warning is written to stderr.
+    #[structopt(long, default_value = "1000")]
+    pub max_nodes: usize,
+
+    /// Common options.
+    #[structopt(flatten)]
+    pub common: CommonGraphOpt,
 }
 
 impl GraphIoOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery graph io 1 | dot -Tsvg > io.svg\n",
+        "    $ mlmdquery get executions --type Trainer --output-template '{id}' | mlmdquery graph io --combine\n",
+    );
+
     /// `$ mlmdquery graph io` implementation.
     pub async fn graph<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
-        let mut store = MetadataStore::connect(&self.db).await?;
+        let executions = resolve_origin_ids(&self.executions)?;
+        let mut store = self.db.connect().await?;
 
-        let origin = NodeId::Execution(ExecutionId::new(self.execution));
-        let mut stack = vec![origin];
-        let mut nodes = HashMap::new();
-        let mut edges = HashSet::new();
-        while let Some(id) = stack.pop() {
-            if nodes.contains_key(&id) {
-                continue;
+        if self.combine {
+            self.generate(&mut store, writer, executions).await?;
+        } else {
+            for execution in executions {
+                self.generate(&mut store, writer, vec![execution]).await?;
             }
+        }
+        Ok(())
+    }
 
-            let node = get_node(&mut store, id).await?;
-            nodes.insert(id, node);
+    async fn generate<W: Write>(
+        &self,
+        store: &mut MetadataStore,
+        writer: &mut W,
+        executions: Vec<i32>,
+    ) -> anyhow::Result<()> {
+        let origins = executions
+            .iter()
+            .map(|&id| NodeId::Execution(ExecutionId::new(id)))
+            .collect::<Vec<_>>();
+        let cache_key = (self.db.url()?, "io", &executions, self.max_nodes);
+        let traversal = if let Some(x) = self.common.load_cache(&cache_key)? {
+            x
+        } else {
+            let x = self
+                .common
+                .traverse(
+                    store,
+                    origins.clone(),
+                    Some(self.max_nodes),
+                    None,
+                    |store, id| Box::pin(get_edges(store, id)),
+                )
+                .await?;
+            if !is_interrupted() {
+                self.common.save_cache(&cache_key, &x)?;
+            }
+            x
+        };
 
-            for edge in get_edges(&mut store, id).await? {
-                stack.push(edge.from_node());
-                stack.push(edge.to_node());
-                edges.insert(edge);
+        let mut graph = Graph::new(
+            store,
+            origins,
+            traversal.nodes,
+            traversal.edges,
+            self.common.clone(),
+        )
+        .await?;
+        if traversal.truncated {
+            if is_interrupted() {
+                eprintln!("warning: the graph was truncated because the traversal was interrupted");
+            } else {
+                eprintln!(
+                    "warning: the graph was truncated because it reached the node limit ({})",
+                    self.max_nodes
+                );
             }
+            graph.prune_dangling_edges();
         }
-
-        let graph = Graph::new(&mut store, origin, nodes, edges, self.url_template.clone()).await?;
         graph.generate(writer)?;
         Ok(())
     }
 }
 
-async fn get_node(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Node> {
-    match id {
-        NodeId::Artifact(id) => {
-            let mut artifacts = store.get_artifacts().id(id).execute().await?;
-            anyhow::ensure!(artifacts.len() == 1, "No such artifact: {}", id.get());
-            Ok(Node::Artifact(artifacts.remove(0)))
-        }
-        NodeId::Execution(id) => {
-            let mut executions = store.get_executions().id(id).execute().await?;
-            anyhow::ensure!(executions.len() == 1, "No such execution: {}", id.get());
-            Ok(Node::Execution(executions.remove(0)))
-        }
-    }
-}
-
 async fn get_edges(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Vec<Edge>> {
     match id {
         NodeId::Artifact(_) => Ok(Vec::new()),
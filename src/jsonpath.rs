@@ -0,0 +1,105 @@
+//! A small subset of [JSONPath](https://goessner.net/articles/JsonPath/) used by `--select`.
+use serde_json::Value;
+
+/// JSONPath expression.
+///
+/// The following syntax is supported:
+/// - `$`: the root (i.e., a record)
+/// - `.name` or `['name']`: a member of an object
+/// - `[N]`: an element of an array
+/// - `.*` or `[*]`: all members or elements
+#[derive(Debug, Clone)]
+pub struct JsonPath {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone)]
+enum Step {
+    Member(String),
+    Index(usize),
+    Wildcard,
+}
+
+impl JsonPath {
+    /// Returns `true` if this path selects at most one value (i.e., it has no wildcards).
+    pub fn is_definite(&self) -> bool {
+        !self.steps.iter().any(|x| matches!(x, Step::Wildcard))
+    }
+
+    /// Returns the values selected by this path.
+    pub fn select<'a>(&self, value: &'a Value) -> Vec<&'a Value> {
+        let mut values = vec![value];
+        for step in &self.steps {
+            values = values
+                .into_iter()
+                .flat_map(|value| match (step, value) {
+                    (Step::Member(name), Value::Object(x)) => x.get(name).into_iter().collect(),
+                    (Step::Index(i), Value::Array(x)) => x.get(*i).into_iter().collect(),
+                    (Step::Wildcard, Value::Object(x)) => x.values().collect(),
+                    (Step::Wildcard, Value::Array(x)) => x.iter().collect(),
+                    _ => Vec::new(),
+                })
+                .collect();
+        }
+        values
+    }
+
+    /// Applies this path to a record.
+    ///
+    /// For a definite path the selected value (or `null` if absent) is returned,
+    /// otherwise an array of the selected values is returned.
+    pub fn apply(&self, record: &Value) -> Value {
+        let mut values = self.select(record);
+        if self.is_definite() {
+            values.pop().cloned().unwrap_or(Value::Null)
+        } else {
+            Value::Array(values.into_iter().cloned().collect())
+        }
+    }
+}
+
+impl std::str::FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut rest = s
+            .strip_prefix('$')
+            .ok_or_else(|| anyhow::anyhow!("JSONPath must start with `$`: {:?}", s))?;
+        let mut steps = Vec::new();
+        while !rest.is_empty() {
+            if let Some(x) = rest.strip_prefix('.') {
+                let end = x.find(['.', '[']).unwrap_or(x.len());
+                let name = &x[..end];
+                anyhow::ensure!(!name.is_empty(), "empty member name in JSONPath: {:?}", s);
+                steps.push(if name == "*" {
+                    Step::Wildcard
+                } else {
+                    Step::Member(name.to_owned())
+                });
+                rest = &x[end..];
+            } else if let Some(x) = rest.strip_prefix('[') {
+                let end = x
+                    .find(']')
+                    .ok_or_else(|| anyhow::anyhow!("unclosed `[` in JSONPath: {:?}", s))?;
+                let inner = x[..end].trim();
+                let quoted = inner
+                    .strip_prefix('\'')
+                    .and_then(|x| x.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|x| x.strip_suffix('"')));
+                steps.push(if inner == "*" {
+                    Step::Wildcard
+                } else if let Some(name) = quoted {
+                    Step::Member(name.to_owned())
+                } else {
+                    Step::Index(inner.parse().map_err(|_| {
+                        anyhow::anyhow!("invalid array index in JSONPath: {:?}", inner)
+                    })?)
+                });
+                rest = &x[end + 1..];
+            } else {
+                anyhow::bail!("unexpected character in JSONPath: {:?}", rest);
+            }
+        }
+        Ok(Self { steps })
+    }
+}
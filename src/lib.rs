@@ -2,13 +2,22 @@
 #![warn(missing_docs)]
 pub mod artifact_types;
 pub mod artifacts;
+mod cache;
 pub mod context_types;
 pub mod contexts;
+pub mod db;
 pub mod derived;
+pub mod diff;
+pub mod error;
 pub mod events;
 pub mod execution_types;
 pub mod executions;
-mod graph;
+pub mod graph;
+pub mod interrupt;
 pub mod io;
+pub mod jsonpath;
 pub mod lineage;
+pub mod neighbors;
+pub mod query;
 mod serialize;
+pub mod validate;
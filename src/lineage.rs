@@ -1,78 +1,77 @@
 //! `$ mlmdquery graph lineage` implementation.
-use crate::graph::{Edge, Graph, Node, NodeId};
+use crate::db::DbOpt;
+use crate::graph::{CommonGraphOpt, Edge, Graph, NodeId};
+use crate::interrupt::is_interrupted;
 use mlmd::metadata::{ArtifactId, EventType};
 use mlmd::requests::EventOrderByField;
 use mlmd::MetadataStore;
-use std::collections::{HashMap, HashSet};
 use std::io::Write;
 
 /// `$ mlmdquery graph lineage` options.
 #[derive(Debug, structopt::StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 pub struct GraphLineageOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    pub db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
 
     /// Target artifact ID.
     pub artifact: i32,
 
-    /// Template to generate node URLs.
-    ///
-    /// You can use the following variables in the template:
-    /// - `{node_type}`: "artifact" or "execution":
-    /// - `{id}`: Artifact or Execution ID (depending on `node_type`)
-    ///
-    /// Please refer to the [tinytemplate](https://docs.rs/tinytemplate/) doc for the features of the template engine.
-    #[structopt(long)]
-    pub url_template: Option<String>,
+    /// Common options.
+    #[structopt(flatten)]
+    pub common: CommonGraphOpt,
 }
 
 impl GraphLineageOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery graph lineage 2 | dot -Tsvg > lineage.svg\n",
+        "    $ mlmdquery graph lineage 2 --url-template 'http://example.com/{node_type}/{id}'\n",
+        "    $ mlmdquery graph lineage 2 --cluster-by context | dot -Tsvg > lineage.svg\n",
+        "    $ mlmdquery graph lineage 2 --node-properties accuracy,loss | dot -Tsvg > lineage.svg\n",
+        "    $ mlmdquery graph lineage 2 --rich-nodes --node-properties accuracy | dot -Tpng > report.png\n",
+    );
+
     /// `$ mlmdquery graph lineage` implementation.
     pub async fn graph<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
-        let mut store = MetadataStore::connect(&self.db).await?;
+        let mut store = self.db.connect().await?;
 
         let origin = NodeId::Artifact(ArtifactId::new(self.artifact));
-        let mut stack = vec![origin];
-        let mut nodes = HashMap::new();
-        let mut edges = HashSet::new();
-        while let Some(id) = stack.pop() {
-            if nodes.contains_key(&id) {
-                continue;
+        let cache_key = (self.db.url()?, "lineage", self.artifact);
+        let traversal = if let Some(x) = self.common.load_cache(&cache_key)? {
+            x
+        } else {
+            let x = self
+                .common
+                .traverse(&mut store, vec![origin], None, None, |store, id| {
+                    Box::pin(get_edges(store, id))
+                })
+                .await?;
+            if !is_interrupted() {
+                self.common.save_cache(&cache_key, &x)?;
             }
+            x
+        };
 
-            let node = get_node(&mut store, id).await?;
-            nodes.insert(id, node);
-
-            for edge in get_edges(&mut store, id).await? {
-                stack.push(edge.from_node());
-                stack.push(edge.to_node());
-                edges.insert(edge);
-            }
+        let mut graph = Graph::new(
+            &mut store,
+            vec![origin],
+            traversal.nodes,
+            traversal.edges,
+            self.common.clone(),
+        )
+        .await?;
+        if traversal.truncated {
+            eprintln!("warning: the graph was truncated because the traversal was interrupted");
+            graph.prune_dangling_edges();
         }
-
-        let graph = Graph::new(&mut store, origin, nodes, edges, self.url_template.clone()).await?;
         graph.generate(writer)?;
         Ok(())
     }
 }
 
-async fn get_node(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Node> {
-    match id {
-        NodeId::Artifact(id) => {
-            let mut artifacts = store.get_artifacts().id(id).execute().await?;
-            anyhow::ensure!(artifacts.len() == 1, "No such artifact: {}", id.get());
-            Ok(Node::Artifact(artifacts.remove(0)))
-        }
-        NodeId::Execution(id) => {
-            let mut executions = store.get_executions().id(id).execute().await?;
-            anyhow::ensure!(executions.len() == 1, "No such execution: {}", id.get());
-            Ok(Node::Execution(executions.remove(0)))
-        }
-    }
-}
-
 async fn get_edges(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Vec<Edge>> {
     match id {
         NodeId::Artifact(id) => {
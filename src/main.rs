@@ -1,9 +1,517 @@
+use anyhow::Context as _;
+use mlmdquery::error::ErrorReport;
+use mlmdquery::jsonpath::JsonPath;
+use mlmdquery::query::{
+    BatchableOpt, FilterExplanation, FilterMode, GetOpt, Order, QueryOutput, QueryResult,
+};
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct Opt {
+    #[structopt(flatten)]
+    output: OutputOpt,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct OutputOpt {
+    /// If specified, a summary of a `get` result (`{"__summary__": {"count": N, "truncated": bool, "order": ...}}`) is written to stderr.
+    ///
+    /// `truncated` is `true` if more items than `--limit` existed (i.e., the next `--offset` would return more items).
+    #[structopt(long, global = true)]
+    emit_summary: bool,
+
+    /// If specified, how the filters of a `get` or `count` command are applied (`{"__explain__": [{"filter": ..., "mode": ...}, ...]}`) is written to stderr.
+    ///
+    /// `mode` is one of "server-side" (pushed down to the DB), "client-side" (applied after fetching all candidates)
+    /// and "client-side-after-limit" (applied after `--limit`, so fewer items than the limit may be returned).
+    #[structopt(long, global = true)]
+    explain: bool,
+
+    /// If specified, JSON is written in a compact single-line form instead of pretty-printed.
+    #[structopt(long, global = true)]
+    compact: bool,
+
+    /// Number of spaces used to indent pretty-printed JSON (default: 2, and 0 means the same as `--compact`).
+    #[structopt(long, global = true, conflicts_with("compact"))]
+    indent: Option<usize>,
+
+    /// Template used to render each record of a `get` result as a line of text instead of JSON.
+    ///
+    /// The fields of a record (e.g., `{id}`, `{type}`, `{uri}`) are available as variables.
+    /// The entries of `properties` and `custom_properties` are also available as top-level
+    /// variables unless they collide with a field name (e.g., `{day}` as well as `{properties.day}`).
+    ///
+    /// Please refer to the [tinytemplate](https://docs.rs/tinytemplate/) doc for the features of the template engine.
+    #[structopt(long, global = true, conflicts_with("compact"))]
+    output_template: Option<String>,
+
+    /// Format of a `get` or `count` result written to stdout.
+    ///
+    /// `csv` writes one row per record with the nested objects (e.g., `properties`) flattened into columns
+    /// (the header is the union of the columns of all records), and a count is written as a single cell.
+    /// `jsonl` (or `ndjson`) writes one JSON record per line.
+    #[structopt(long, global = true, default_value = "json", possible_values = OutputFormat::POSSIBLE_VALUES)]
+    output_format: OutputFormat,
+
+    /// File to which a `get` result is written instead of stdout (can be specified multiple times).
+    ///
+    /// The format is inferred from the extension (`.json`, `.csv`, or `.jsonl` / `.ndjson` for one JSON record per line).
+    /// If the path additionally ends with `.gz` (e.g., `artifacts.jsonl.gz`), the file is gzip-compressed.
+    #[structopt(long = "output", global = true, conflicts_with("output-template"))]
+    outputs: Vec<PathBuf>,
+
+    /// String written for `null` (and missing) values in CSV output (JSON output is not affected).
+    #[structopt(long, global = true, default_value = "")]
+    null_as: String,
+
+    /// Separator between a field name and a key of a nested object (e.g., `properties.day`) in CSV column names.
+    ///
+    /// `\` and the separator in a key are escaped with `\` (e.g., the `a.b` key of `properties` becomes `properties.a\.b`).
+    #[structopt(long, global = true, default_value = ".")]
+    csv_key_separator: String,
+
+    /// JSONPath expression applied to each record of a `get` result (e.g., `$.properties.day`).
+    ///
+    /// Only a subset of JSONPath is supported: `$`, `.name`, `['name']`, `[N]`, `.*` and `[*]`.
+    /// For a path without wildcards the selected value (or `null`) is output per record,
+    /// otherwise an array of the selected values is output per record.
+    #[structopt(long, global = true, conflicts_with_all(&["output-template", "outputs"]))]
+    select: Option<JsonPath>,
+
+    /// If specified, a `get` or `count` result is wrapped as `{"query": ..., "generated_at": ..., "results": ...}` in JSON output.
+    ///
+    /// `query` is the command options (in the form accepted by `batch`, with the database password masked)
+    /// and `generated_at` is a UNIX timestamp (seconds).
+    #[structopt(long, global = true, conflicts_with("output-template"))]
+    envelope: bool,
+
+    /// If specified, a `get` command is re-run until it returns at least one item (or `--wait-timeout` elapses).
+    ///
+    /// This is useful to read back items just written to a DB with replication lag.
+    #[structopt(long, global = true)]
+    wait_for_results: bool,
+
+    /// Maximum time to wait for results (e.g., `30s`, `500ms` or `2m`; seconds if the unit is omitted).
+    ///
+    /// If no results are returned within this time, the command fails.
+    #[structopt(long, global = true, default_value = "30s", parse(try_from_str = parse_duration))]
+    wait_timeout: std::time::Duration,
+
+    /// Interval between the queries of `--wait-for-results` (e.g., `2s`).
+    #[structopt(long, global = true, default_value = "2s", parse(try_from_str = parse_duration))]
+    poll_interval: std::time::Duration,
+
+    /// Format of an error written to stderr.
+    ///
+    /// If `json` is specified, an error is written as
+    /// `{"error": {"kind": ..., "mlmd_error": ..., "message": ..., "causes": [...]}}`
+    /// where `kind` is one of "NotFound", "ConnectionError", "InvalidArgument" and "Other".
+    #[structopt(long, global = true, default_value = "text", possible_values = ErrorFormat::POSSIBLE_VALUES)]
+    error_format: ErrorFormat,
+}
+
+impl OutputOpt {
+    // Rejects the output options that would be ignored by `command` (or by the output format).
+    //
+    // `matches` tells whether an option is explicitly specified (even if it has a default value).
+    fn validate(
+        &self,
+        command: &Command,
+        matches: &structopt::clap::ArgMatches,
+    ) -> Result<(), structopt::clap::Error> {
+        for path in &self.outputs {
+            if let Err(e) = OutputFormat::from_path(path) {
+                return Err(structopt::clap::Error::with_description(
+                    &e.to_string(),
+                    structopt::clap::ErrorKind::InvalidValue,
+                ));
+            }
+        }
+
+        let as_graph = matches!(
+            command,
+            Command::Batchable(BatchableOpt::Get(GetOpt::Events(x))) if x.as_graph
+        );
+        let get = !as_graph && matches!(command, Command::Batchable(BatchableOpt::Get(_)));
+        let get_or_count = !as_graph && matches!(command, Command::Batchable(_));
+        let produces_json =
+            !as_graph && !matches!(command, Command::Graph(_) | Command::Completions(_));
+        let json_format = matches!(self.output_format, OutputFormat::Json);
+        let csv = get_or_count
+            && (matches!(self.output_format, OutputFormat::Csv)
+                || self
+                    .outputs
+                    .iter()
+                    .any(|x| matches!(OutputFormat::from_path(x), Ok(OutputFormat::Csv))));
+
+        // (argument name, flag, whether the flag is meaningful here, where the flag can be used)
+        let rules = [
+            (
+                "outputs",
+                "--output",
+                get && json_format,
+                "`get` commands with `--output-format json`",
+            ),
+            (
+                "output-format",
+                "--output-format",
+                get_or_count,
+                "`get` and `count` commands (not `get events --as-graph`)",
+            ),
+            (
+                "output-template",
+                "--output-template",
+                get && json_format,
+                "`get` commands with `--output-format json`",
+            ),
+            (
+                "select",
+                "--select",
+                get && json_format,
+                "`get` commands with `--output-format json`",
+            ),
+            (
+                "envelope",
+                "--envelope",
+                get_or_count && json_format,
+                "`get` and `count` commands with `--output-format json`",
+            ),
+            ("emit-summary", "--emit-summary", get, "`get` commands"),
+            (
+                "wait-for-results",
+                "--wait-for-results",
+                get,
+                "`get` commands",
+            ),
+            (
+                "wait-timeout",
+                "--wait-timeout",
+                self.wait_for_results,
+                "`--wait-for-results`",
+            ),
+            (
+                "poll-interval",
+                "--poll-interval",
+                self.wait_for_results,
+                "`--wait-for-results`",
+            ),
+            (
+                "explain",
+                "--explain",
+                matches!(command, Command::Batchable(_)),
+                "`get` and `count` commands",
+            ),
+            (
+                "compact",
+                "--compact",
+                produces_json,
+                "commands producing JSON (not `graph`, `completions` or `get events --as-graph`)",
+            ),
+            (
+                "indent",
+                "--indent",
+                produces_json,
+                "commands producing JSON (not `graph`, `completions` or `get events --as-graph`)",
+            ),
+            (
+                "null-as",
+                "--null-as",
+                csv,
+                "CSV output (`--output-format csv` or an `--output` path ending with `.csv`)",
+            ),
+            (
+                "csv-key-separator",
+                "--csv-key-separator",
+                csv,
+                "CSV output (`--output-format csv` or an `--output` path ending with `.csv`)",
+            ),
+        ];
+        for (name, flag, allowed, scope) in rules {
+            if matches.occurrences_of(name) > 0 && !allowed {
+                return Err(structopt::clap::Error::with_description(
+                    &format!("`{}` can only be used with {}", flag, scope),
+                    structopt::clap::ErrorKind::ArgumentConflict,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    // Re-runs `command` until it returns at least one item.
+    async fn wait_for_results(&self, command: &BatchableOpt) -> anyhow::Result<QueryOutput> {
+        let deadline = tokio::time::Instant::now() + self.wait_timeout;
+        loop {
+            let output = command.execute().await?;
+            if output.result.item_count() != Some(0) {
+                return Ok(output);
+            }
+            if tokio::time::Instant::now() + self.poll_interval > deadline {
+                anyhow::bail!(
+                    "no results were returned within {}s",
+                    self.wait_timeout.as_secs_f64()
+                );
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    fn write_json<T: serde::Serialize>(&self, value: &T) -> anyhow::Result<()> {
+        self.write_json_to(std::io::stdout().lock(), value)
+    }
+
+    fn write_json_to<W: Write, T: serde::Serialize>(
+        &self,
+        mut writer: W,
+        value: &T,
+    ) -> anyhow::Result<()> {
+        let indent = self.indent.unwrap_or(2);
+        if self.compact || indent == 0 {
+            serde_json::to_writer(&mut writer, value)?;
+        } else {
+            let indent = " ".repeat(indent);
+            let formatter = serde_json::ser::PrettyFormatter::with_indent(indent.as_bytes());
+            let mut serializer = serde_json::Serializer::with_formatter(&mut writer, formatter);
+            value.serialize(&mut serializer)?;
+        }
+        writeln!(writer)?;
+        Ok(())
+    }
+
+    // Writes `results` wrapped in an `Envelope` if `--envelope` is specified.
+    fn write_results_to<W: Write, T: serde::Serialize>(
+        &self,
+        writer: W,
+        command: &BatchableOpt,
+        results: &T,
+    ) -> anyhow::Result<()> {
+        if self.envelope {
+            let generated_at = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs_f64();
+            let mut query = serde_json::to_value(command)?;
+            redact_db_urls(&mut query);
+            let envelope = Envelope {
+                query,
+                generated_at,
+                results,
+            };
+            self.write_json_to(writer, &envelope)
+        } else {
+            self.write_json_to(writer, results)
+        }
+    }
+
+    fn write_files(&self, command: &BatchableOpt, result: &QueryResult) -> anyhow::Result<()> {
+        for path in &self.outputs {
+            let format = OutputFormat::from_path(path)?;
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("failed to create {}", path.display()))?;
+            let mut writer = std::io::BufWriter::new(file);
+            if is_gzip(path) {
+                let mut encoder =
+                    flate2::write::GzEncoder::new(writer, flate2::Compression::default());
+                let written = self.write_file(&mut encoder, format, command, result);
+
+                // The encoder is finished even on error so that the partial output is still a valid gzip file.
+                let finished = encoder.finish().and_then(|mut x| x.flush());
+                written?;
+                finished.with_context(|| format!("failed to write {}", path.display()))?;
+            } else {
+                self.write_file(&mut writer, format, command, result)?;
+                writer
+                    .flush()
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_file<W: Write>(
+        &self,
+        writer: W,
+        format: OutputFormat,
+        command: &BatchableOpt,
+        result: &QueryResult,
+    ) -> anyhow::Result<()> {
+        match format {
+            OutputFormat::Json => self.write_results_to(writer, command, result),
+            OutputFormat::Csv => result.write_csv(writer, &self.null_as, &self.csv_key_separator),
+            OutputFormat::Jsonl => write_jsonl_to(writer, result),
+        }
+    }
+
+    fn write_template(&self, template: &str, result: &QueryResult) -> anyhow::Result<()> {
+        let mut tt = tinytemplate::TinyTemplate::new();
+        tt.set_default_formatter(&tinytemplate::format_unescaped);
+        tt.add_template("record", template)?;
+
+        let records = match result.to_json()? {
+            serde_json::Value::Array(records) => records,
+            value => vec![value],
+        };
+        for mut record in records {
+            if let serde_json::Value::Object(fields) = &mut record {
+                for key in ["properties", "custom_properties"] {
+                    if let Some(serde_json::Value::Object(properties)) = fields.get(key).cloned() {
+                        for (name, value) in properties {
+                            fields.entry(name).or_insert(value);
+                        }
+                    }
+                }
+            }
+            println!("{}", tt.render("record", &record)?);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OutputFormat {
+    Json,
+    Csv,
+    Jsonl,
+}
+
+impl OutputFormat {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["json", "csv", "jsonl", "ndjson"];
+
+    fn from_path(path: &Path) -> anyhow::Result<Self> {
+        let uncompressed = if is_gzip(path) {
+            path.with_extension("")
+        } else {
+            path.to_owned()
+        };
+        uncompressed
+            .extension()
+            .and_then(|x| x.to_str())
+            .and_then(|x| x.to_ascii_lowercase().parse().ok())
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "cannot infer the output format of {} (expected `.json`, `.csv`, `.jsonl` or `.ndjson`, optionally followed by `.gz`)",
+                    path.display()
+                )
+            })
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "json" => Ok(Self::Json),
+            "csv" => Ok(Self::Csv),
+            "jsonl" | "ndjson" => Ok(Self::Jsonl),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+fn is_gzip(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .is_some_and(|x| x.eq_ignore_ascii_case("gz"))
+}
+
+// Writes each record of `result` as a line of compact JSON (a count is written as `{"count": N}`).
+fn write_jsonl_to<W: Write>(mut writer: W, result: &QueryResult) -> anyhow::Result<()> {
+    let records = match result {
+        QueryResult::Count(count) => vec![serde_json::json!({ "count": count })],
+        _ => match result.to_json()? {
+            serde_json::Value::Array(records) => records,
+            value => vec![value],
+        },
+    };
+    for record in records {
+        serde_json::to_writer(&mut writer, &record)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+// Parses a duration such as `30s`, `500ms` or `2m` (seconds if the unit is omitted).
+fn parse_duration(s: &str) -> anyhow::Result<std::time::Duration> {
+    let (value, scale) = if let Some(x) = s.strip_suffix("ms") {
+        (x, 0.001)
+    } else if let Some(x) = s.strip_suffix('s') {
+        (x, 1.0)
+    } else if let Some(x) = s.strip_suffix('m') {
+        (x, 60.0)
+    } else {
+        (s, 1.0)
+    };
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .with_context(|| format!("invalid duration: {:?}", s))?;
+    std::time::Duration::try_from_secs_f64(value * scale)
+        .with_context(|| format!("invalid duration: {:?}", s))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ErrorFormat {
+    Text,
+    Json,
+}
+
+impl ErrorFormat {
+    const POSSIBLE_VALUES: &'static [&'static str] = &["text", "json"];
+}
+
+impl std::str::FromStr for ErrorFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Envelope<'a, T> {
+    query: serde_json::Value,
+    generated_at: f64,
+    results: &'a T,
+}
+
+// Line of `--ndjson` output or element of `--keep-going` output.
+#[derive(Debug, serde::Serialize)]
+struct BatchRecord {
+    index: usize,
+    #[serde(flatten)]
+    outcome: BatchOutcome,
+}
+
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BatchOutcome {
+    // Result of a command executed without `--keep-going`.
+    Result(serde_json::Value),
+    Ok(serde_json::Value),
+    Error(String),
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ErrorHeader {
+    error: ErrorReport,
+}
+
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 #[allow(clippy::large_enum_variant)]
-enum Opt {
+enum Command {
     #[allow(missing_docs)]
     #[structopt(flatten)]
     Batchable(BatchableOpt),
@@ -11,218 +519,509 @@ enum Opt {
     /// Generates graphs in DOT language.
     Graph(GraphOpt),
 
+    /// Compares artifacts or executions.
+    Diff(DiffOpt),
+
     /// Executes a batch of commands.
     Batch(BatchOpt),
+
+    /// Checks the referential integrity of the DB (exits with a non-zero status if any issue is found).
+    #[structopt(after_help = mlmdquery::validate::ValidateOpt::EXAMPLES)]
+    Validate(mlmdquery::validate::ValidateOpt),
+
+    /// Generates a shell completion script.
+    Completions(CompletionsOpt),
+}
+
+impl Command {
+    // Returns whether Ctrl-C should stop the command and output the partial result
+    // (only `get` and `graph` commands, whose partial results are still meaningful).
+    fn is_interruptible(&self) -> bool {
+        matches!(self, Self::Batchable(BatchableOpt::Get(_)) | Self::Graph(_))
+    }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+struct CompletionsOpt {
+    /// Target shell.
+    #[structopt(possible_values = &structopt::clap::Shell::variants(), case_insensitive = true)]
+    shell: structopt::clap::Shell,
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
 struct BatchOpt {
-    /// Database URL.
-    #[structopt(long, env = "MLMD_DB", hide_env_values = true)]
-    db: String,
+    /// Database options.
+    #[structopt(flatten)]
+    db: mlmdquery::db::DbOpt,
 
-    /// Number of worker threads.
+    /// Number of worker threads (per database).
     #[structopt(long, default_value = "10")]
     workers: std::num::NonZeroUsize,
 
+    /// If specified, the number of completed commands is shown on stderr during the execution.
+    #[structopt(long)]
+    progress: bool,
+
+    /// If specified, each result is written as a line of JSON (`{"index": N, "result": ...}`) as soon as it is ready.
+    ///
+    /// The lines are written in order of completion, so use `index` (the position of the command) to re-order them.
+    #[structopt(long)]
+    ndjson: bool,
+
+    /// If specified, a failed command does not abort the batch.
+    ///
+    /// Each result is written as `{"index": N, "ok": ...}` or `{"index": N, "error": "..."}` instead
+    /// (also in `--ndjson` lines), and the process exits with a non-zero status if any command failed.
+    #[structopt(long)]
+    keep_going: bool,
+
+    /// File containing newline-delimited JSON commands (`-` means stdin).
+    ///
+    /// The commands in the file are executed after the ones given as arguments. Empty lines are ignored.
+    #[structopt(long)]
+    commands_file: Option<PathBuf>,
+
     /// Commands to be executed.
+    ///
+    /// A command can specify its own database by `"db"` or `"db-file"`, otherwise the database of the batch is used.
     commands: Vec<BatchableOpt>,
 }
 
 impl BatchOpt {
-    async fn execute(&self) -> anyhow::Result<()> {
-        let handlers = (0..self.workers.get())
-            .map(|i| {
-                let db = self.db.clone();
-                let commands = self
-                    .commands
+    // Returns the commands given as arguments followed by the ones in `--commands-file`.
+    fn commands(&self) -> anyhow::Result<Vec<BatchableOpt>> {
+        let mut commands = self.commands.clone();
+        let path = if let Some(path) = &self.commands_file {
+            path
+        } else {
+            return Ok(commands);
+        };
+        let (text, name) = if path == Path::new("-") {
+            (std::io::read_to_string(std::io::stdin())?, "stdin".into())
+        } else {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path.display()))?;
+            (text, path.display().to_string())
+        };
+        for (i, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let command = line
+                .parse()
+                .with_context(|| format!("invalid command at line {} of {}", i + 1, name))?;
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+
+    async fn execute(&self, output: &OutputOpt) -> anyhow::Result<()> {
+        let commands = self.commands()?;
+        let progress = if self.progress {
+            let progress = indicatif::ProgressBar::new(commands.len() as u64);
+            progress.set_style(indicatif::ProgressStyle::with_template(
+                "{bar:40} {pos}/{len} commands completed",
+            )?);
+            progress
+        } else {
+            indicatif::ProgressBar::hidden()
+        };
+        // Commands specifying their own database are executed against it instead of `--db`.
+        let mut groups = BTreeMap::<String, (mlmdquery::db::DbOpt, Vec<_>)>::new();
+        for (i, command) in commands.iter().enumerate() {
+            let db = if command.db().is_specified() {
+                let mut db = command.db().clone();
+                db.connect_timeout = db.connect_timeout.or(self.db.connect_timeout);
+                db
+            } else {
+                self.db.clone()
+            };
+            groups
+                .entry(db.url()?)
+                .or_insert_with(|| (db, Vec::new()))
+                .1
+                .push((i, command.clone()));
+        }
+
+        // With `--ndjson`, the results are sent to a single writer task so that lines are never interleaved.
+        let (sender, writer) = if self.ndjson {
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+            let writer = tokio::spawn(async move {
+                let mut stdout = std::io::stdout();
+                let mut failures = 0;
+                while let Some((index, outcome)) = receiver.recv().await {
+                    if matches!(outcome, BatchOutcome::Error(_)) {
+                        failures += 1;
+                    }
+                    let line = serde_json::to_string(&BatchRecord { index, outcome })?;
+                    writeln!(stdout, "{}", line)?;
+                    stdout.flush()?;
+                }
+                Ok::<_, anyhow::Error>(failures)
+            });
+            (Some(sender), Some(writer))
+        } else {
+            (None, None)
+        };
+
+        let mut handlers = Vec::<tokio::task::JoinHandle<anyhow::Result<_>>>::new();
+        for (db, commands) in groups.into_values() {
+            let workers = self.workers.get().min(commands.len());
+            for i in 0..workers {
+                let db = db.clone();
+                let progress = progress.clone();
+                let sender = sender.clone();
+                let keep_going = self.keep_going;
+                let commands = commands
                     .iter()
                     .enumerate()
                     .filter_map(|(j, c)| {
-                        if j % self.workers.get() == i {
-                            Some((j, c.clone()))
+                        if j % workers == i {
+                            Some(c.clone())
                         } else {
                             None
                         }
                     })
                     .collect::<Vec<_>>();
-                tokio::spawn(async move {
-                    let mut store = mlmd::MetadataStore::connect(&db).await?;
+                handlers.push(tokio::spawn(async move {
+                    let mut store = match db.connect().await {
+                        Ok(store) => Ok(store),
+                        Err(e) if keep_going => Err(format!("{:#}", e)),
+                        Err(e) => return Err(e),
+                    };
                     let mut results = Vec::new();
+                    let mut cursors = Vec::new();
                     for (i, command) in commands {
-                        let result = command.execute_with_store(&mut store).await?;
-                        results.push((i, result));
+                        let result = match &mut store {
+                            Ok(store) => command.execute_with_store(store).await.and_then(|x| {
+                                cursors.extend(x.cursor);
+                                x.result.to_json()
+                            }),
+                            Err(e) => Err(anyhow::anyhow!("{}", e)),
+                        };
+                        let outcome = match result {
+                            Ok(x) if keep_going => BatchOutcome::Ok(x),
+                            Ok(x) => BatchOutcome::Result(x),
+                            Err(e) if keep_going => BatchOutcome::Error(format!("{:#}", e)),
+                            Err(e) => return Err(e),
+                        };
+                        if let Some(sender) = &sender {
+                            // The receiver is dropped only if the writer task failed, which is reported below.
+                            let _ = sender.send((i, outcome));
+                        } else {
+                            results.push((i, outcome));
+                        }
+                        progress.inc(1);
                     }
-                    Ok(results)
-                })
-            })
-            .collect::<Vec<tokio::task::JoinHandle<anyhow::Result<_>>>>();
+                    Ok((results, cursors))
+                }));
+            }
+        }
 
         let mut results = Vec::new();
+        let mut cursors = Vec::new();
         for handler in handlers {
-            results.extend(handler.await??);
-        }
-        results.sort_by_key(|x| x.0);
-        serde_json::to_writer_pretty(
-            std::io::stdout().lock(),
-            &results.into_iter().map(|x| x.1).collect::<Vec<_>>(),
-        )?;
-        println!();
+            let (x, y) = handler.await??;
+            results.extend(x);
+            cursors.extend(y);
+        }
+        progress.finish_and_clear();
+        drop(sender);
+        let failures = if let Some(writer) = writer {
+            writer.await??
+        } else {
+            results.sort_by_key(|x| x.0);
+            let failures = results
+                .iter()
+                .filter(|x| matches!(x.1, BatchOutcome::Error(_)))
+                .count();
+            if self.keep_going {
+                let records = results
+                    .into_iter()
+                    .map(|(index, outcome)| BatchRecord { index, outcome })
+                    .collect::<Vec<_>>();
+                output.write_json(&records)?;
+            } else {
+                let values = results
+                    .into_iter()
+                    .filter_map(|x| match x.1 {
+                        BatchOutcome::Result(x) => Some(x),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                output.write_json(&values)?;
+            }
+            failures
+        };
+
+        // The `--cursor-file`s are updated only after the results are written.
+        std::io::stdout().flush()?;
+        for cursor in cursors {
+            cursor.save()?;
+        }
+        if failures > 0 {
+            anyhow::bail!("{} of {} commands failed", failures, commands.len());
+        }
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, StructOpt, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
-#[serde(rename_all = "kebab-case")]
-enum BatchableOpt {
-    /// Counts artifacts/executions/contexts/events.
-    Count(CountOpt),
+enum GraphOpt {
+    /// Generates a graph showing the lineage of an artifact.
+    #[structopt(after_help = mlmdquery::lineage::GraphLineageOpt::EXAMPLES)]
+    Lineage(mlmdquery::lineage::GraphLineageOpt),
 
-    /// Gets artifacts/executions/contexts/events.
-    Get(GetOpt),
-}
+    /// Generates a graph showing the artifacts derived from artifacts.
+    #[structopt(after_help = mlmdquery::derived::GraphDerivedOpt::EXAMPLES)]
+    Derived(mlmdquery::derived::GraphDerivedOpt),
 
-impl std::str::FromStr for BatchableOpt {
-    type Err = anyhow::Error;
+    /// Generates a graph showing the input and output of an execution.
+    #[structopt(after_help = mlmdquery::io::GraphIoOpt::EXAMPLES)]
+    Io(mlmdquery::io::GraphIoOpt),
 
-    fn from_str(s: &str) -> anyhow::Result<Self> {
-        let v = serde_json::from_str(s)?;
-        Ok(v)
-    }
+    /// Generates a graph showing the nodes within N hops of an execution.
+    #[structopt(after_help = mlmdquery::neighbors::GraphNeighborsOpt::EXAMPLES)]
+    Neighbors(mlmdquery::neighbors::GraphNeighborsOpt),
 }
 
-impl BatchableOpt {
-    fn db_uri(&self) -> &str {
-        match self {
-            Self::Count(CountOpt::Artifacts(opt)) => &opt.common.db,
-            Self::Get(GetOpt::Artifacts(opt)) => &opt.common.db,
-            Self::Count(CountOpt::ArtifactTypes(opt)) => &opt.db,
-            Self::Get(GetOpt::ArtifactTypes(opt)) => &opt.db,
-            Self::Count(CountOpt::Executions(opt)) => &opt.common.db,
-            Self::Get(GetOpt::Executions(opt)) => &opt.common.db,
-            Self::Count(CountOpt::ExecutionTypes(opt)) => &opt.db,
-            Self::Get(GetOpt::ExecutionTypes(opt)) => &opt.db,
-            Self::Count(CountOpt::Contexts(opt)) => &opt.common.db,
-            Self::Get(GetOpt::Contexts(opt)) => &opt.common.db,
-            Self::Count(CountOpt::ContextTypes(opt)) => &opt.db,
-            Self::Get(GetOpt::ContextTypes(opt)) => &opt.db,
-            Self::Count(CountOpt::Events(opt)) => &opt.common.db,
-            Self::Get(GetOpt::Events(opt)) => &opt.common.db,
-        }
-    }
-
-    async fn execute(&self) -> anyhow::Result<serde_json::Value> {
-        let mut store = mlmd::MetadataStore::connect(self.db_uri()).await?;
-        self.execute_with_store(&mut store).await
-    }
-
-    async fn execute_with_store(
-        &self,
-        store: &mut mlmd::MetadataStore,
-    ) -> anyhow::Result<serde_json::Value> {
-        match self {
-            Self::Count(CountOpt::Artifacts(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::Artifacts(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::ArtifactTypes(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::ArtifactTypes(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::Executions(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::Executions(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::ExecutionTypes(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::ExecutionTypes(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::Contexts(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::Contexts(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::ContextTypes(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::ContextTypes(opt)) => to_json(opt.get(store).await?),
-            Self::Count(CountOpt::Events(opt)) => to_json(opt.count(store).await?),
-            Self::Get(GetOpt::Events(opt)) => to_json(opt.get(store).await?),
-        }
-    }
-}
-
-#[derive(Debug, Clone, StructOpt, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, StructOpt)]
 #[structopt(rename_all = "kebab-case")]
-#[serde(rename_all = "kebab-case")]
-enum CountOpt {
-    /// Counts artifacts.
-    Artifacts(mlmdquery::artifacts::CountArtifactsOpt),
-
-    /// Counts artifact types.
-    ArtifactTypes(mlmdquery::artifact_types::ArtifactTypesOpt),
-
-    /// Counts executions.
-    Executions(mlmdquery::executions::CountExecutionsOpt),
-
-    /// Counts execution types.
-    ExecutionTypes(mlmdquery::execution_types::ExecutionTypesOpt),
-
-    /// Counts contexts.
-    Contexts(mlmdquery::contexts::CountContextsOpt),
+enum DiffOpt {
+    /// Compares the artifacts attributed to two contexts.
+    Artifacts(mlmdquery::diff::DiffArtifactsOpt),
 
-    /// Counts context types.
-    ContextTypes(mlmdquery::context_types::ContextTypesOpt),
+    /// Compares the properties of two artifacts.
+    #[structopt(after_help = mlmdquery::diff::DiffPropertiesOpt::EXAMPLES)]
+    Artifact(mlmdquery::diff::DiffPropertiesOpt),
 
-    /// Counts events.
-    Events(mlmdquery::events::CountEventsOpt),
+    /// Compares the properties of two executions.
+    #[structopt(after_help = mlmdquery::diff::DiffPropertiesOpt::EXAMPLES)]
+    Execution(mlmdquery::diff::DiffPropertiesOpt),
 }
 
-#[derive(Debug, Clone, StructOpt, serde::Serialize, serde::Deserialize)]
-#[structopt(rename_all = "kebab-case")]
-#[serde(rename_all = "kebab-case")]
-enum GetOpt {
-    /// Gets artifacts.
-    Artifacts(mlmdquery::artifacts::GetArtifactsOpt),
-
-    /// Gets artifact types.
-    ArtifactTypes(mlmdquery::artifact_types::ArtifactTypesOpt),
-
-    /// Gets executions.
-    Executions(mlmdquery::executions::GetExecutionsOpt),
-
-    /// Gets execution types.
-    ExecutionTypes(mlmdquery::execution_types::ExecutionTypesOpt),
+#[derive(Debug, serde::Serialize)]
+struct SummaryFooter {
+    #[serde(rename = "__summary__")]
+    summary: Summary,
+}
 
-    /// Gets contexts.
-    Contexts(mlmdquery::contexts::GetContextsOpt),
+#[derive(Debug, serde::Serialize)]
+struct ExplanationHeader<'a> {
+    #[serde(rename = "__explain__")]
+    explain: &'a [FilterExplanation],
+}
 
-    /// Gets context types.
-    ContextTypes(mlmdquery::context_types::ContextTypesOpt),
+#[derive(Debug, serde::Serialize)]
+struct Summary {
+    count: usize,
+    truncated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    order: Option<Order>,
+}
 
-    /// Gets events.
-    Events(mlmdquery::events::GetEventsOpt),
+#[tokio::main]
+async fn main() {
+    let matches = Opt::clap().get_matches();
+    let opt = Opt::from_clap(&matches);
+    if let Err(e) = opt.output.validate(&opt.command, &matches) {
+        e.exit();
+    }
+    let error_format = opt.output.error_format;
+    if opt.command.is_interruptible() {
+        mlmdquery::interrupt::install_handler();
+    }
+    if let Err(e) = run(opt).await {
+        match error_format {
+            ErrorFormat::Text => eprintln!("Error: {:?}", e),
+            ErrorFormat::Json => {
+                let header = ErrorHeader {
+                    error: ErrorReport::new(&e),
+                };
+                match serde_json::to_string(&header) {
+                    Ok(json) => eprintln!("{}", json),
+                    Err(_) => eprintln!("Error: {:?}", e),
+                }
+            }
+        }
+        std::process::exit(1);
+    }
+    if mlmdquery::interrupt::is_interrupted() {
+        // The partial result has been output, but the command didn't complete.
+        let _ = std::io::stdout().flush();
+        std::process::exit(130);
+    }
 }
 
-#[derive(Debug, StructOpt)]
-#[structopt(rename_all = "kebab-case")]
-enum GraphOpt {
-    /// Generates a graph showing the lineage of an artifact.
-    Lineage(mlmdquery::lineage::GraphLineageOpt),
+async fn run(opt: Opt) -> anyhow::Result<()> {
+    match opt.command {
+        Command::Batchable(ref command @ BatchableOpt::Get(GetOpt::Events(ref events)))
+            if events.as_graph =>
+        {
+            if opt.output.explain {
+                write_explanation(command)?;
+            }
+            let mut store = command.db().connect().await?;
+            events
+                .graph(&mut store, &mut std::io::stdout().lock())
+                .await?;
+        }
+        Command::Batchable(command) => {
+            if opt.output.explain {
+                write_explanation(&command)?;
+            }
+            let QueryOutput {
+                result,
+                truncated,
+                cursor,
+            } = if opt.output.wait_for_results {
+                opt.output.wait_for_results(&command).await?
+            } else {
+                command.execute().await?
+            };
+            if let Some(template) = &opt.output.output_template {
+                opt.output.write_template(template, &result)?;
+            } else if !opt.output.outputs.is_empty() {
+                opt.output.write_files(&command, &result)?;
+            } else if let Some(path) = &opt.output.select {
+                let selected = match result.to_json()? {
+                    serde_json::Value::Array(records) => {
+                        records.iter().map(|x| path.apply(x)).collect()
+                    }
+                    value => vec![path.apply(&value)],
+                };
+                opt.output
+                    .write_results_to(std::io::stdout().lock(), &command, &selected)?;
+            } else {
+                opt.output.write_file(
+                    std::io::stdout().lock(),
+                    opt.output.output_format,
+                    &command,
+                    &result,
+                )?;
+            }
+            if let Some(cursor) = cursor {
+                std::io::stdout().flush()?;
+                cursor.save()?;
+            }
+            if opt.output.emit_summary {
+                write_summary(&command, &result, truncated)?;
+            }
+        }
+        Command::Batch(command) => command.execute(&opt.output).await?,
+        Command::Validate(command) => {
+            let issues = command.validate().await?;
+            opt.output.write_json(&issues)?;
+            if !issues.is_empty() {
+                std::io::stdout().flush()?;
+                std::process::exit(1);
+            }
+        }
+        Command::Diff(DiffOpt::Artifacts(command)) => {
+            opt.output.write_json(&command.diff().await?)?
+        }
+        Command::Diff(DiffOpt::Artifact(command)) => {
+            opt.output.write_json(&command.diff_artifacts().await?)?
+        }
+        Command::Diff(DiffOpt::Execution(command)) => {
+            opt.output.write_json(&command.diff_executions().await?)?
+        }
+        Command::Completions(command) => Opt::clap().gen_completions_to(
+            env!("CARGO_PKG_NAME"),
+            command.shell,
+            &mut std::io::stdout().lock(),
+        ),
+        Command::Graph(GraphOpt::Lineage(command)) => {
+            command.graph(&mut std::io::stdout().lock()).await?
+        }
+        Command::Graph(GraphOpt::Derived(command)) => {
+            command.graph(&mut std::io::stdout().lock()).await?
+        }
+        Command::Graph(GraphOpt::Io(command)) => {
+            command.graph(&mut std::io::stdout().lock()).await?
+        }
+        Command::Graph(GraphOpt::Neighbors(command)) => {
+            command.graph(&mut std::io::stdout().lock()).await?
+        }
+    }
+    Ok(())
+}
 
-    /// Generates a graph showing the artifacts derived from an artifact.
-    Derived(mlmdquery::derived::GraphDerivedOpt),
+// Masks the passwords in the database URLs (`"db"`) so that they are not saved with the results.
+fn redact_db_urls(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (name, value) in fields {
+                match value {
+                    serde_json::Value::String(url) if name == "db" => {
+                        *url = redact_password(url);
+                    }
+                    _ => redact_db_urls(value),
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(redact_db_urls),
+        _ => {}
+    }
+}
 
-    /// Generates a graph showing the input and output of an execution.
-    Io(mlmdquery::io::GraphIoOpt),
+fn redact_password(url: &str) -> String {
+    let (scheme, rest) = match url.split_once("://") {
+        Some(x) => x,
+        None => return url.to_owned(),
+    };
+    let authority_end = rest.find('/').unwrap_or(rest.len());
+    match rest[..authority_end].rsplit_once('@') {
+        Some((userinfo, host)) => match userinfo.split_once(':') {
+            Some((user, _)) => format!(
+                "{}://{}:***@{}{}",
+                scheme,
+                user,
+                host,
+                &rest[authority_end..]
+            ),
+            None => url.to_owned(),
+        },
+        None => url.to_owned(),
+    }
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let opt = Opt::from_args();
-    match opt {
-        Opt::Batchable(opt) => {
-            serde_json::to_writer_pretty(std::io::stdout().lock(), &opt.execute().await?)?;
-            println!();
-        }
-        Opt::Batch(opt) => opt.execute().await?,
-        Opt::Graph(GraphOpt::Lineage(opt)) => opt.graph(&mut std::io::stdout().lock()).await?,
-        Opt::Graph(GraphOpt::Derived(opt)) => opt.graph(&mut std::io::stdout().lock()).await?,
-        Opt::Graph(GraphOpt::Io(opt)) => opt.graph(&mut std::io::stdout().lock()).await?,
+fn write_explanation(command: &BatchableOpt) -> anyhow::Result<()> {
+    let filters = command.explain();
+    serde_json::to_writer(
+        std::io::stderr().lock(),
+        &ExplanationHeader { explain: &filters },
+    )?;
+    eprintln!();
+    if let Some(limit) = command.limit() {
+        for x in &filters {
+            if x.mode == FilterMode::ClientSideAfterLimit {
+                eprintln!(
+                    "warning: `{}` is applied after `--limit {}`, so the result may contain fewer items than the limit",
+                    x.filter, limit
+                );
+            }
+        }
     }
     Ok(())
 }
 
-fn to_json(item: impl serde::Serialize) -> anyhow::Result<serde_json::Value> {
-    let v = serde_json::to_value(&item)?;
-    Ok(v)
+fn write_summary(
+    command: &BatchableOpt,
+    result: &QueryResult,
+    truncated: bool,
+) -> anyhow::Result<()> {
+    if let Some(count) = result.item_count() {
+        let summary = Summary {
+            count,
+            truncated,
+            order: command.order(),
+        };
+        serde_json::to_writer(std::io::stderr().lock(), &SummaryFooter { summary })?;
+        eprintln!();
+    }
+    Ok(())
 }
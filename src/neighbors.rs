@@ -0,0 +1,86 @@
+//! `$ mlmdquery graph neighbors` implementation.
+use crate::db::DbOpt;
+use crate::graph::{CommonGraphOpt, Edge, Graph, NodeId};
+use crate::interrupt::is_interrupted;
+use mlmd::metadata::ExecutionId;
+use mlmd::MetadataStore;
+use std::io::Write;
+
+/// `$ mlmdquery graph neighbors` options.
+#[derive(Debug, structopt::StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct GraphNeighborsOpt {
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
+
+    /// Target execution ID.
+    #[structopt(long)]
+    pub execution: i32,
+
+    /// Maximum number of hops from the target execution.
+    #[structopt(long, default_value = "1")]
+    pub radius: usize,
+
+    /// Common options.
+    #[structopt(flatten)]
+    pub common: CommonGraphOpt,
+}
+
+impl GraphNeighborsOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery graph neighbors --execution 1 --radius 2 | dot -Tsvg > neighbors.svg\n",
+    );
+
+    /// `$ mlmdquery graph neighbors` implementation.
+    pub async fn graph<W: Write>(&self, writer: &mut W) -> anyhow::Result<()> {
+        let mut store = self.db.connect().await?;
+
+        let origin = NodeId::Execution(ExecutionId::new(self.execution));
+        let cache_key = (self.db.url()?, "neighbors", self.execution, self.radius);
+        let traversal = if let Some(x) = self.common.load_cache(&cache_key)? {
+            x
+        } else {
+            let x = self
+                .common
+                .traverse(
+                    &mut store,
+                    vec![origin],
+                    None,
+                    Some(self.radius),
+                    |store, id| Box::pin(get_edges(store, id)),
+                )
+                .await?;
+            if !is_interrupted() {
+                self.common.save_cache(&cache_key, &x)?;
+            }
+            x
+        };
+
+        let mut graph = Graph::new(
+            &mut store,
+            vec![origin],
+            traversal.nodes,
+            traversal.edges,
+            self.common.clone(),
+        )
+        .await?;
+        graph.set_boundaries(traversal.boundaries);
+        if traversal.truncated {
+            eprintln!("warning: the graph was truncated because the traversal was interrupted");
+            graph.prune_dangling_edges();
+        }
+        graph.generate(writer)?;
+        Ok(())
+    }
+}
+
+async fn get_edges(store: &mut MetadataStore, id: NodeId) -> anyhow::Result<Vec<Edge>> {
+    let events = match id {
+        NodeId::Artifact(id) => store.get_events().artifact(id).execute().await?,
+        NodeId::Execution(id) => store.get_events().execution(id).execute().await?,
+    };
+    Ok(events.into_iter().map(Edge::new).collect())
+}
@@ -0,0 +1,1001 @@
+//! `$ mlmdquery {get,count} *` dispatching.
+use crate::db::DbOpt;
+use crate::events::{EventFormat, PendingCursor};
+use crate::graph::TimeZone;
+use anyhow::Context as _;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::time::Duration;
+
+pub use crate::serialize::{
+    Artifact, ArtifactState, CollapsedEvent, Context, Event, EventEdge, EventStep, EventType,
+    Execution, ExecutionState, ExecutionSummary, PropertyType, PropertyValue, Timestamp, Type,
+};
+
+/// `$ mlmdquery {get,count} *` options.
+#[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+pub enum BatchableOpt {
+    /// Counts artifacts/executions/contexts/events.
+    Count(CountOpt),
+
+    /// Gets artifacts/executions/contexts/events.
+    Get(GetOpt),
+}
+
+impl std::str::FromStr for BatchableOpt {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let v = serde_json::from_str(s)?;
+        Ok(v)
+    }
+}
+
+impl BatchableOpt {
+    /// Returns the database options of this command.
+    pub fn db(&self) -> &DbOpt {
+        match self {
+            Self::Count(CountOpt::Artifacts(opt)) => &opt.common.db,
+            Self::Get(GetOpt::Artifacts(opt)) => &opt.common.db,
+            Self::Count(CountOpt::ArtifactTypes(opt)) => &opt.db,
+            Self::Get(GetOpt::ArtifactTypes(opt)) => &opt.db,
+            Self::Count(CountOpt::Executions(opt)) => &opt.common.db,
+            Self::Get(GetOpt::Executions(opt)) => &opt.common.db,
+            Self::Count(CountOpt::ExecutionTypes(opt)) => &opt.db,
+            Self::Get(GetOpt::ExecutionTypes(opt)) => &opt.db,
+            Self::Count(CountOpt::Contexts(opt)) => &opt.common.db,
+            Self::Get(GetOpt::Contexts(opt)) => &opt.common.db,
+            Self::Count(CountOpt::ContextTypes(opt)) => &opt.db,
+            Self::Get(GetOpt::ContextTypes(opt)) => &opt.db,
+            Self::Count(CountOpt::Events(opt)) => &opt.common.db,
+            Self::Get(GetOpt::Events(opt)) => &opt.common.db,
+        }
+    }
+
+    /// Returns the maximum number of items in a search result if this command has the limit.
+    pub fn limit(&self) -> Option<usize> {
+        let limit = match self {
+            Self::Get(GetOpt::Artifacts(opt)) => opt.limit,
+            Self::Get(GetOpt::Executions(opt)) => opt.limit,
+            Self::Get(GetOpt::Contexts(opt)) => opt.limit,
+            Self::Get(GetOpt::Events(opt)) => opt.limit,
+            _ => return None,
+        };
+        Some(limit).filter(|&n| n != 0)
+    }
+
+    /// Returns how the filters specified to this command are applied.
+    pub fn explain(&self) -> Vec<FilterExplanation> {
+        match self {
+            Self::Count(CountOpt::Artifacts(opt)) => opt.common.explain(),
+            Self::Get(GetOpt::Artifacts(opt)) => opt.explain(),
+            Self::Count(CountOpt::Executions(opt)) => opt.common.explain(),
+            Self::Get(GetOpt::Executions(opt)) => opt.explain(),
+            Self::Count(CountOpt::Contexts(opt)) => opt.common.explain(),
+            Self::Get(GetOpt::Contexts(opt)) => opt.explain(),
+            Self::Count(CountOpt::Events(opt)) => opt.common.explain(),
+            Self::Get(GetOpt::Events(opt)) => opt.explain(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns the sort order of a search result if this command sorts it.
+    pub fn order(&self) -> Option<Order> {
+        let (field, asc) = match self {
+            Self::Get(GetOpt::Artifacts(opt)) if opt.preserve_order => ("id-arguments", true),
+            Self::Get(GetOpt::Executions(opt)) if opt.preserve_order => ("id-arguments", true),
+            Self::Get(GetOpt::Contexts(opt)) if opt.preserve_order => ("id-arguments", true),
+            Self::Get(GetOpt::Artifacts(opt)) if opt.order_by_property.is_some() => {
+                ("property", opt.asc)
+            }
+            Self::Get(GetOpt::Artifacts(opt)) => (opt.order_by.as_str(), opt.asc),
+            Self::Get(GetOpt::Executions(opt)) => (opt.order_by.as_str(), opt.asc),
+            Self::Get(GetOpt::Contexts(opt)) if opt.activity => ("activity", opt.asc),
+            Self::Get(GetOpt::Contexts(opt)) => (opt.order_by.as_str(), opt.asc),
+            Self::Get(GetOpt::Events(opt)) => ("ctime", opt.asc || opt.cursor_file.is_some()),
+            _ => return None,
+        };
+        let direction = if asc { "asc" } else { "desc" };
+        Some(Order { field, direction })
+    }
+
+    /// Connects to the database and executes this command.
+    ///
+    /// If `--db-glob` is specified, this command is executed against each shard and the results are merged.
+    pub async fn execute(&self) -> anyhow::Result<QueryOutput> {
+        if self.db().db_glob.is_some() {
+            return self.execute_sharded().await;
+        }
+        let mut store = self.db().connect().await?;
+        self.execute_with_store(&mut store).await
+    }
+
+    async fn execute_sharded(&self) -> anyhow::Result<QueryOutput> {
+        if let Self::Get(GetOpt::Events(opt)) = self {
+            anyhow::ensure!(
+                opt.cursor_file.is_none(),
+                "`--cursor-file` cannot be used with `--db-glob`"
+            );
+        }
+        let mut merged = None;
+        let mut truncated = false;
+        for url in self.db().shard_urls()? {
+            let output = async {
+                let mut store = self.db().connect_to(&url).await?;
+                self.execute_with_store(&mut store).await
+            }
+            .await
+            .with_context(|| format!("failed to query {}", url))?;
+            truncated |= output.truncated;
+            merged = Some(match merged {
+                None => output.result,
+                Some(x) => QueryResult::merge(x, output.result)?,
+            });
+        }
+        let result = merged.ok_or_else(|| anyhow::anyhow!("no shard was queried"))?;
+        Ok(QueryOutput {
+            result,
+            truncated,
+            cursor: None,
+        })
+    }
+
+    /// Executes this command using the given store.
+    pub async fn execute_with_store(
+        &self,
+        store: &mut mlmd::MetadataStore,
+    ) -> anyhow::Result<QueryOutput> {
+        let mut cursor = None;
+        let mut truncated = false;
+        let result = match self {
+            Self::Count(CountOpt::Artifacts(opt)) if opt.per_context => {
+                QueryResult::GroupedCount(opt.count_per_context(store).await?)
+            }
+            Self::Count(CountOpt::Artifacts(opt)) => {
+                if let Some(group_by) = opt.group_by_field() {
+                    QueryResult::GroupedCount(opt.count_groups(store, group_by).await?)
+                } else {
+                    QueryResult::Count(opt.count(store).await?)
+                }
+            }
+            Self::Get(GetOpt::Artifacts(opt)) if opt.group_into_contexts => {
+                QueryResult::GroupedArtifacts(opt.get_grouped_by_contexts(store).await?)
+            }
+            Self::Get(GetOpt::Artifacts(opt)) => {
+                let page = opt.get_page(store).await?;
+                truncated = page.truncated;
+                QueryResult::Artifacts(page.items)
+            }
+            Self::Count(CountOpt::ArtifactTypes(opt)) => {
+                QueryResult::Count(opt.count(store).await?)
+            }
+            Self::Get(GetOpt::ArtifactTypes(opt)) => QueryResult::Types(opt.get(store).await?),
+            Self::Count(CountOpt::Executions(opt)) if opt.count_by_state => {
+                QueryResult::GroupedCount(opt.count_states(store).await?)
+            }
+            Self::Count(CountOpt::Executions(opt)) => QueryResult::Count(opt.count(store).await?),
+            Self::Get(GetOpt::Executions(opt)) => {
+                let page = opt.get_page(store).await?;
+                truncated = page.truncated;
+                QueryResult::Executions(page.items)
+            }
+            Self::Count(CountOpt::ExecutionTypes(opt)) => {
+                QueryResult::Count(opt.count(store).await?)
+            }
+            Self::Get(GetOpt::ExecutionTypes(opt)) => QueryResult::Types(opt.get(store).await?),
+            Self::Count(CountOpt::Contexts(opt)) => QueryResult::Count(opt.count(store).await?),
+            Self::Get(GetOpt::Contexts(opt)) => {
+                let page = opt.get_page(store).await?;
+                truncated = page.truncated;
+                QueryResult::Contexts(page.items)
+            }
+            Self::Count(CountOpt::ContextTypes(opt)) => QueryResult::Count(opt.count(store).await?),
+            Self::Get(GetOpt::ContextTypes(opt)) => QueryResult::Types(opt.get(store).await?),
+            Self::Count(CountOpt::Events(opt)) => QueryResult::Count(opt.count(store).await?),
+            Self::Get(GetOpt::Events(opt)) if opt.as_graph => {
+                anyhow::bail!("`--as-graph` cannot be used in `$ mlmdquery batch`")
+            }
+            Self::Get(GetOpt::Events(opt)) => match opt.format {
+                EventFormat::Full if opt.collapse_steps => {
+                    let (page, x) = opt.get_collapsed(store).await?;
+                    cursor = x;
+                    truncated = page.truncated;
+                    QueryResult::CollapsedEvents(page.items)
+                }
+                EventFormat::Full => {
+                    let (page, x) = opt.get(store).await?;
+                    cursor = x;
+                    truncated = page.truncated;
+                    QueryResult::Events(page.items)
+                }
+                EventFormat::EdgeList => {
+                    anyhow::ensure!(
+                        !opt.collapse_steps,
+                        "`--collapse-steps` cannot be used with `--format edge-list`"
+                    );
+                    let (page, x) = opt.get_edges(store).await?;
+                    cursor = x;
+                    truncated = page.truncated;
+                    QueryResult::EventEdges(page.items)
+                }
+            },
+        };
+        Ok(QueryOutput {
+            result,
+            truncated,
+            cursor,
+        })
+    }
+}
+
+/// `$ mlmdquery count *` options.
+#[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+#[allow(clippy::large_enum_variant)]
+pub enum CountOpt {
+    /// Counts artifacts.
+    Artifacts(crate::artifacts::CountArtifactsOpt),
+
+    /// Counts artifact types.
+    ArtifactTypes(crate::artifact_types::ArtifactTypesOpt),
+
+    /// Counts executions.
+    Executions(crate::executions::CountExecutionsOpt),
+
+    /// Counts execution types.
+    ExecutionTypes(crate::execution_types::ExecutionTypesOpt),
+
+    /// Counts contexts.
+    Contexts(crate::contexts::CountContextsOpt),
+
+    /// Counts context types.
+    ContextTypes(crate::context_types::ContextTypesOpt),
+
+    /// Counts events.
+    Events(crate::events::CountEventsOpt),
+}
+
+/// `$ mlmdquery get *` options.
+#[derive(Debug, Clone, structopt::StructOpt, serde::Serialize, serde::Deserialize)]
+#[structopt(rename_all = "kebab-case")]
+#[serde(rename_all = "kebab-case")]
+#[allow(clippy::large_enum_variant)]
+pub enum GetOpt {
+    /// Gets artifacts.
+    #[structopt(after_help = crate::artifacts::GetArtifactsOpt::EXAMPLES)]
+    Artifacts(crate::artifacts::GetArtifactsOpt),
+
+    /// Gets artifact types.
+    ArtifactTypes(crate::artifact_types::ArtifactTypesOpt),
+
+    /// Gets executions.
+    #[structopt(after_help = crate::executions::GetExecutionsOpt::EXAMPLES)]
+    Executions(crate::executions::GetExecutionsOpt),
+
+    /// Gets execution types.
+    ExecutionTypes(crate::execution_types::ExecutionTypesOpt),
+
+    /// Gets contexts.
+    #[structopt(after_help = crate::contexts::GetContextsOpt::EXAMPLES)]
+    Contexts(crate::contexts::GetContextsOpt),
+
+    /// Gets context types.
+    ContextTypes(crate::context_types::ContextTypesOpt),
+
+    /// Gets events.
+    #[structopt(after_help = crate::events::GetEventsOpt::EXAMPLES)]
+    Events(crate::events::GetEventsOpt),
+}
+
+/// Sort order of a search result.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Order {
+    /// Field used to sort the result.
+    pub field: &'static str,
+
+    /// "asc" or "desc".
+    pub direction: &'static str,
+}
+
+/// Unit of the timestamps in a search result.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[allow(missing_docs)]
+pub enum TimeUnit {
+    #[default]
+    #[serde(rename = "s")]
+    Seconds,
+    #[serde(rename = "ms")]
+    Milliseconds,
+}
+
+impl TimeUnit {
+    pub(crate) const POSSIBLE_VALUES: &'static [&'static str] = &["s", "ms"];
+
+    pub(crate) fn timestamp(self, time_since_epoch: Duration) -> Timestamp {
+        match self {
+            Self::Seconds => Timestamp::Seconds(time_since_epoch.as_secs_f64()),
+            Self::Milliseconds => Timestamp::Milliseconds(time_since_epoch.as_millis() as u64),
+        }
+    }
+}
+
+impl std::str::FromStr for TimeUnit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "s" => Ok(Self::Seconds),
+            "ms" => Ok(Self::Milliseconds),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+/// Where a filter is applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FilterMode {
+    /// The filter is pushed down to the DB.
+    ServerSide,
+
+    /// The filter is applied after fetching all candidates from the DB
+    /// (`--limit` and `--offset` are applied to the filtered result).
+    ClientSide,
+
+    /// The filter is applied to the result fetched with `--limit` and `--offset`
+    /// (fewer items than the limit may be returned).
+    ClientSideAfterLimit,
+}
+
+/// How a filter is applied.
+#[derive(Debug, serde::Serialize)]
+pub struct FilterExplanation {
+    /// Command-line option of the filter.
+    pub filter: &'static str,
+
+    /// Where the filter is applied.
+    pub mode: FilterMode,
+}
+
+// Returns the explanations of the filters that are enabled (i.e., the second element is `true`).
+pub(crate) fn explain_filters(
+    filters: &[(&'static str, bool, FilterMode)],
+) -> Vec<FilterExplanation> {
+    filters
+        .iter()
+        .filter(|x| x.1)
+        .map(|&(filter, _, mode)| FilterExplanation { filter, mode })
+        .collect()
+}
+
+// Splits `s` into a key and a value at the first `=` that isn't escaped by `\`.
+//
+// `\=` and `\\` in the key are unescaped into `=` and `\` respectively.
+pub(crate) fn split_key_value(s: &str) -> Option<(String, &str)> {
+    let mut key = String::new();
+    let mut chars = s.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, c @ ('=' | '\\'))) => key.push(c),
+                Some((_, c)) => {
+                    key.push('\\');
+                    key.push(c);
+                }
+                None => key.push('\\'),
+            },
+            '=' => return Some((key, &s[i + 1..])),
+            _ => key.push(c),
+        }
+    }
+    None
+}
+
+// Removes the duplicates in `ids` keeping the first occurrences.
+pub(crate) fn dedup_ids(ids: &[i32]) -> Vec<i32> {
+    let mut seen = HashSet::new();
+    ids.iter().copied().filter(|id| seen.insert(*id)).collect()
+}
+
+// Stably sorts `items` in the order of `ids` for `--preserve-order`.
+//
+// The items whose IDs are not in `ids` are placed at the end.
+pub(crate) fn sort_by_id_order<T>(items: &mut [T], ids: &[i32], id: impl Fn(&T) -> i32) {
+    let positions = dedup_ids(ids)
+        .into_iter()
+        .enumerate()
+        .map(|(i, id)| (id, i))
+        .collect::<HashMap<_, _>>();
+    items.sort_by_key(|x| positions.get(&id(x)).copied().unwrap_or(usize::MAX));
+}
+
+// Applies `--offset` and `--limit` (`0` means no limit) to the items filtered or sorted on the client side.
+pub(crate) fn paginate<T>(items: Vec<T>, offset: usize, limit: usize) -> Page<T> {
+    let mut items = items.into_iter().skip(offset).collect::<Vec<_>>();
+    let truncated = truncate_to_limit(&mut items, limit);
+    Page { items, truncated }
+}
+
+// Truncates `items` to `limit` items (`0` means no limit), returning whether any item was dropped.
+pub(crate) fn truncate_to_limit<T>(items: &mut Vec<T>, limit: usize) -> bool {
+    let truncated = limit != 0 && items.len() > limit;
+    if truncated {
+        items.truncate(limit);
+    }
+    truncated
+}
+
+// Shuffles `items` for `--order-by random`.
+//
+// The same `seed` always yields the same order for the same input.
+pub(crate) fn shuffle<T>(items: &mut [T], seed: Option<u64>) {
+    use rand::seq::SliceRandom as _;
+    use rand::SeedableRng as _;
+
+    if let Some(seed) = seed {
+        items.shuffle(&mut rand::rngs::StdRng::seed_from_u64(seed));
+    } else {
+        items.shuffle(&mut rand::thread_rng());
+    }
+}
+
+// Returns whether a `count` command has to fetch all the candidates to apply client-side filters
+// (the DB-side count doesn't reflect them), warning about the extra cost if so.
+pub(crate) fn count_needs_fetch(filters: &[FilterExplanation]) -> bool {
+    let client_side = filters
+        .iter()
+        .filter(|x| x.mode != FilterMode::ServerSide)
+        .map(|x| x.filter)
+        .collect::<Vec<_>>();
+    if client_side.is_empty() {
+        return false;
+    }
+    eprintln!(
+        "warning: `{}` cannot be counted by the DB, so all the candidates are fetched (this may be slow)",
+        client_side.join("`, `")
+    );
+    true
+}
+
+/// How to output non-finite (`NaN` or infinite) double property values, which JSON cannot represent.
+#[derive(Debug, Default, Clone, Copy, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[allow(missing_docs)]
+pub enum NonFinitePolicy {
+    #[default]
+    Null,
+    String,
+    Error,
+}
+
+impl NonFinitePolicy {
+    pub(crate) const POSSIBLE_VALUES: &'static [&'static str] = &["null", "string", "error"];
+}
+
+impl std::str::FromStr for NonFinitePolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "null" => Ok(Self::Null),
+            "string" => Ok(Self::String),
+            "error" => Ok(Self::Error),
+            _ => anyhow::bail!("invalid value: {:?}", s),
+        }
+    }
+}
+
+/// Point in time given to a time range filter (e.g., `--ctime-start`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum TimePoint {
+    /// UNIX timestamp seconds.
+    Seconds(f64),
+
+    /// Date string (e.g., `2021-08-10`, `2021-08-10 12:00:00` or `2021-08-10T12:00:00+09:00`).
+    Date(String),
+}
+
+impl TimePoint {
+    pub(crate) fn to_duration(&self, timezone: TimeZone) -> anyhow::Result<Duration> {
+        match self {
+            Self::Seconds(x) => Ok(Duration::from_secs_f64(*x)),
+            Self::Date(x) => timezone.parse(x),
+        }
+    }
+}
+
+impl std::str::FromStr for TimePoint {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        if let Ok(x) = s.parse() {
+            return Ok(Self::Seconds(x));
+        }
+        TimeZone::Utc.parse(s)?;
+        Ok(Self::Date(s.to_owned()))
+    }
+}
+
+/// Property value predicate given to a property filter (`NAME=VALUE`, e.g., `--property split=train`).
+///
+/// VALUE is parsed as an int, a double or a string (in that order).
+/// VALUE prefixed with `@` is read from the file at the following path (e.g., `config_hash=@hash.txt`).
+/// `=` in NAME can be escaped as `\=` (and `\` as `\\`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PropertyFilter {
+    spec: String,
+    name: String,
+    text: String,
+    value: PropertyValue,
+}
+
+impl PropertyFilter {
+    // Returns whether `properties` has the property whose value equals to the value of this filter.
+    //
+    // Numbers are compared numerically (e.g., `1` matches `1.0`) and strings are compared with the text of VALUE as is.
+    pub(crate) fn matches(&self, properties: &mlmd::metadata::PropertyValues) -> bool {
+        use mlmd::metadata::PropertyValue as V;
+
+        match (properties.get(&self.name), &self.value) {
+            (Some(V::String(x)), _) => *x == self.text,
+            (Some(V::Int(x)), PropertyValue::Int(y)) => x == y,
+            (Some(V::Int(x)), PropertyValue::Double(y)) => f64::from(*x) == *y,
+            (Some(V::Double(x)), PropertyValue::Int(y)) => *x == f64::from(*y),
+            (Some(V::Double(x)), PropertyValue::Double(y)) => x == y,
+            _ => false,
+        }
+    }
+}
+
+impl std::str::FromStr for PropertyFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, text) = split_key_value(s)
+            .ok_or_else(|| anyhow::anyhow!("expected `NAME=VALUE`, but got {:?}", s))?;
+        let text = if let Some(path) = text.strip_prefix('@') {
+            let text = std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read {}", path))?;
+            text.strip_suffix('\n').unwrap_or(&text).to_owned()
+        } else {
+            text.to_owned()
+        };
+        let value = if let Ok(x) = text.parse() {
+            PropertyValue::Int(x)
+        } else if let Ok(x) = text.parse() {
+            PropertyValue::Double(x)
+        } else {
+            PropertyValue::String(text.clone())
+        };
+        Ok(Self {
+            spec: s.to_owned(),
+            name,
+            text,
+            value,
+        })
+    }
+}
+
+impl TryFrom<String> for PropertyFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<PropertyFilter> for String {
+    fn from(x: PropertyFilter) -> Self {
+        x.spec
+    }
+}
+
+/// Regular expression given to a client-side filter (e.g., `--name-regex`).
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Regex(regex::Regex);
+
+impl Regex {
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        self.0.is_match(s)
+    }
+}
+
+impl std::str::FromStr for Regex {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        Ok(Self(regex::Regex::new(s)?))
+    }
+}
+
+impl TryFrom<String> for Regex {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<Regex> for String {
+    fn from(x: Regex) -> Self {
+        x.0.as_str().to_owned()
+    }
+}
+
+/// Filter on a value inside a JSON string property (`NAME:POINTER OP VALUE`, e.g., `metrics:/accuracy>0.9`).
+///
+/// POINTER is a JSON Pointer (e.g., `/scores/0`, or empty for the whole value) applied to the property value parsed as JSON.
+/// OP is one of `=` (or `==`), `!=`, `<`, `<=`, `>` and `>=`.
+/// VALUE is parsed as JSON (e.g., `0.9`, `true` or `"text"`), or used as a string if it is not valid JSON.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JsonPropertyFilter {
+    spec: String,
+    name: String,
+    pointer: String,
+    op: CompareOp,
+    value: serde_json::Value,
+}
+
+impl JsonPropertyFilter {
+    // Returns whether the property (looked up in `properties` first, then in `custom_properties`) is
+    // a JSON string whose value at the pointer satisfies the condition.
+    //
+    // Numbers are compared numerically and strings lexicographically.
+    // Other values can only be compared by `=` and `!=`.
+    pub(crate) fn matches(
+        &self,
+        properties: &mlmd::metadata::PropertyValues,
+        custom_properties: &mlmd::metadata::PropertyValues,
+    ) -> bool {
+        use serde_json::Value;
+        use std::cmp::Ordering;
+
+        let json = match properties
+            .get(&self.name)
+            .or_else(|| custom_properties.get(&self.name))
+        {
+            Some(mlmd::metadata::PropertyValue::String(x)) => x,
+            _ => return false,
+        };
+        let json = match serde_json::from_str::<Value>(json) {
+            Ok(json) => json,
+            Err(_) => return false,
+        };
+        let actual = match json.pointer(&self.pointer) {
+            Some(x) => x,
+            None => return false,
+        };
+        let ordering = match (actual, &self.value) {
+            (Value::Number(a), Value::Number(b)) => a.as_f64().partial_cmp(&b.as_f64()),
+            (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+            (a, b) if a == b => Some(Ordering::Equal),
+            _ => None,
+        };
+        match self.op {
+            CompareOp::Eq => ordering == Some(Ordering::Equal),
+            CompareOp::Ne => ordering != Some(Ordering::Equal),
+            CompareOp::Lt => ordering == Some(Ordering::Less),
+            CompareOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            CompareOp::Gt => ordering == Some(Ordering::Greater),
+            CompareOp::Ge => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+impl std::str::FromStr for JsonPropertyFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let (name, rest) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("expected `NAME:POINTER OP VALUE`, but got {:?}", s))?;
+        let i = rest
+            .find(['=', '!', '<', '>'])
+            .ok_or_else(|| anyhow::anyhow!("no comparison operator in {:?}", s))?;
+        let pointer = &rest[..i];
+        anyhow::ensure!(
+            pointer.is_empty() || pointer.starts_with('/'),
+            "JSON pointer must be empty or start with `/`, but got {:?}",
+            pointer
+        );
+        let (op, len) = match &rest.as_bytes()[i..] {
+            [b'=', b'=', ..] => (CompareOp::Eq, 2),
+            [b'=', ..] => (CompareOp::Eq, 1),
+            [b'!', b'=', ..] => (CompareOp::Ne, 2),
+            [b'<', b'=', ..] => (CompareOp::Le, 2),
+            [b'<', ..] => (CompareOp::Lt, 1),
+            [b'>', b'=', ..] => (CompareOp::Ge, 2),
+            [b'>', ..] => (CompareOp::Gt, 1),
+            _ => anyhow::bail!("invalid comparison operator in {:?}", s),
+        };
+        let text = rest[i + len..].trim();
+        let value = serde_json::from_str(text)
+            .unwrap_or_else(|_| serde_json::Value::String(text.to_owned()));
+        Ok(Self {
+            spec: s.to_owned(),
+            name: name.to_owned(),
+            pointer: pointer.to_owned(),
+            op,
+            value,
+        })
+    }
+}
+
+impl TryFrom<String> for JsonPropertyFilter {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<JsonPropertyFilter> for String {
+    fn from(x: JsonPropertyFilter) -> Self {
+        x.spec
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// SQL LIKE pattern given to a client-side filter (e.g., `--uri-pattern`).
+///
+/// `%` matches any sequence of characters and `_` matches any single character.
+/// Unlike some DB collations, the matching is case-sensitive.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct LikePattern {
+    pattern: String,
+    regex: regex::Regex,
+}
+
+impl LikePattern {
+    pub(crate) fn is_match(&self, s: &str) -> bool {
+        self.regex.is_match(s)
+    }
+}
+
+impl std::str::FromStr for LikePattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        anyhow::ensure!(!s.is_empty(), "empty pattern is not allowed");
+
+        let mut regex = String::from("(?s)^");
+        let mut literal = [0; 4];
+        for c in s.chars() {
+            match c {
+                '%' => regex.push_str(".*"),
+                '_' => regex.push('.'),
+                _ => regex.push_str(&regex::escape(c.encode_utf8(&mut literal))),
+            }
+        }
+        regex.push('$');
+        Ok(Self {
+            pattern: s.to_owned(),
+            regex: regex::Regex::new(&regex)?,
+        })
+    }
+}
+
+impl TryFrom<String> for LikePattern {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> anyhow::Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<LikePattern> for String {
+    fn from(x: LikePattern) -> Self {
+        x.pattern
+    }
+}
+
+/// Items returned by a `get` command.
+#[derive(Debug)]
+pub struct Page<T> {
+    /// Items after `--offset` and `--limit` are applied.
+    pub items: Vec<T>,
+
+    /// Whether more items than `--limit` existed (i.e., the next `--offset` would return more items).
+    ///
+    /// Filters applied after the limit (e.g., `--roots`) may shrink `items`, but they don't affect this flag.
+    pub truncated: bool,
+}
+
+/// Result of a `$ mlmdquery {get,count} *` command and the state to be saved after the result is written.
+#[derive(Debug)]
+pub struct QueryOutput {
+    /// Result of the command.
+    pub result: QueryResult,
+
+    /// Whether more items than `--limit` existed (always `false` for `count` commands).
+    pub truncated: bool,
+
+    /// `--cursor-file` position of `get events` (if any).
+    ///
+    /// Save this only after the result is written, so that a failed write does not skip the events.
+    pub cursor: Option<PendingCursor>,
+}
+
+/// Result of a `$ mlmdquery {get,count} *` command.
+#[derive(Debug, serde::Serialize)]
+#[serde(untagged)]
+pub enum QueryResult {
+    /// Number of the items.
+    Count(usize),
+
+    /// Number of the items per group (e.g., `count artifacts --per-context`).
+    GroupedCount(BTreeMap<String, usize>),
+
+    /// Artifacts per context ID (`get artifacts --group-into-contexts`).
+    GroupedArtifacts(BTreeMap<i32, Vec<Artifact>>),
+
+    /// Artifacts.
+    Artifacts(Vec<Artifact>),
+
+    /// Executions.
+    Executions(Vec<Execution>),
+
+    /// Contexts.
+    Contexts(Vec<Context>),
+
+    /// Artifact, execution or context types.
+    Types(Vec<Type>),
+
+    /// Events.
+    Events(Vec<Event>),
+
+    /// Events collapsed by `--collapse-steps`.
+    CollapsedEvents(Vec<CollapsedEvent>),
+
+    /// Events as graph edges (`--format edge-list`).
+    EventEdges(Vec<EventEdge>),
+}
+
+impl QueryResult {
+    /// Merges the results of the same command executed against different shards (`--db-glob`).
+    ///
+    /// Items are concatenated as they are, so IDs of different shards may collide and cannot be compared.
+    /// Types are the exception: those having the same name are merged into one
+    /// (their properties and usages are combined, and the ID in the first shard is kept).
+    fn merge(self, other: Self) -> anyhow::Result<Self> {
+        Ok(match (self, other) {
+            (Self::Count(a), Self::Count(b)) => Self::Count(a + b),
+            (Self::GroupedCount(mut a), Self::GroupedCount(b)) => {
+                for (k, n) in b {
+                    *a.entry(k).or_default() += n;
+                }
+                Self::GroupedCount(a)
+            }
+            (Self::GroupedArtifacts(mut a), Self::GroupedArtifacts(b)) => {
+                for (k, x) in b {
+                    a.entry(k).or_default().extend(x);
+                }
+                Self::GroupedArtifacts(a)
+            }
+            (Self::Artifacts(mut a), Self::Artifacts(b)) => {
+                a.extend(b);
+                Self::Artifacts(a)
+            }
+            (Self::Executions(mut a), Self::Executions(b)) => {
+                a.extend(b);
+                Self::Executions(a)
+            }
+            (Self::Contexts(mut a), Self::Contexts(b)) => {
+                a.extend(b);
+                Self::Contexts(a)
+            }
+            (Self::Types(mut a), Self::Types(b)) => {
+                for y in b {
+                    if let Some(x) = a.iter_mut().find(|x| x.name == y.name) {
+                        x.properties.extend(y.properties);
+                        if let Some(usage) = y.usage {
+                            let merged = x.usage.get_or_insert_with(BTreeMap::new);
+                            for (k, n) in usage {
+                                *merged.entry(k).or_default() += n;
+                            }
+                        }
+                    } else {
+                        a.push(y);
+                    }
+                }
+                Self::Types(a)
+            }
+            (Self::Events(mut a), Self::Events(b)) => {
+                a.extend(b);
+                Self::Events(a)
+            }
+            (Self::CollapsedEvents(mut a), Self::CollapsedEvents(b)) => {
+                a.extend(b);
+                Self::CollapsedEvents(a)
+            }
+            (Self::EventEdges(mut a), Self::EventEdges(b)) => {
+                a.extend(b);
+                Self::EventEdges(a)
+            }
+            _ => anyhow::bail!("cannot merge results of different kinds"),
+        })
+    }
+
+    /// Returns the number of items in this result (`None` for a count result).
+    pub fn item_count(&self) -> Option<usize> {
+        match self {
+            Self::Count(_) | Self::GroupedCount(_) | Self::GroupedArtifacts(_) => None,
+            Self::Artifacts(x) => Some(x.len()),
+            Self::Executions(x) => Some(x.len()),
+            Self::Contexts(x) => Some(x.len()),
+            Self::Types(x) => Some(x.len()),
+            Self::Events(x) => Some(x.len()),
+            Self::CollapsedEvents(x) => Some(x.len()),
+            Self::EventEdges(x) => Some(x.len()),
+        }
+    }
+
+    /// Converts this result into a JSON value.
+    pub fn to_json(&self) -> anyhow::Result<serde_json::Value> {
+        let v = serde_json::to_value(self)?;
+        Ok(v)
+    }
+
+    /// Writes this result as CSV.
+    ///
+    /// `null` values are written as `null_as`, and the keys of nested objects (e.g., `properties`)
+    /// are joined to their field names with `key_separator`.
+    pub fn write_csv<W: std::io::Write>(
+        &self,
+        writer: W,
+        null_as: &str,
+        key_separator: &str,
+    ) -> anyhow::Result<()> {
+        crate::serialize::csv::write(writer, &self.to_json()?, null_as, key_separator)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_key_value_works() {
+        assert_eq!(split_key_value(r"a\=b=c"), Some(("a=b".to_owned(), "c")));
+        assert_eq!(split_key_value("a.b=c"), Some(("a.b".to_owned(), "c")));
+        assert_eq!(
+            split_key_value("key with space=v"),
+            Some(("key with space".to_owned(), "v"))
+        );
+        assert_eq!(split_key_value(r"a\\=b"), Some((r"a\".to_owned(), "b")));
+        assert_eq!(split_key_value(r"a\"), None);
+        assert_eq!(split_key_value(r"a=b\"), Some(("a".to_owned(), r"b\")));
+        assert_eq!(split_key_value("abc"), None);
+    }
+
+    #[test]
+    fn csv_header_escapes_keys() -> anyhow::Result<()> {
+        let value = serde_json::json!([{
+            "id": 1,
+            "properties": {"a.b": 1, "k=v": 2, "with space": 3, r"back\slash": 4}
+        }]);
+        let mut buf = Vec::new();
+        crate::serialize::csv::write(&mut buf, &value, "", ".")?;
+        assert_eq!(
+            String::from_utf8(buf)?.lines().next(),
+            Some(r"id,properties.a\.b,properties.back\\slash,properties.k=v,properties.with space")
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,25 @@
-use chrono::{DateTime, Local};
-use std::collections::BTreeMap;
-use std::time::SystemTime;
+//! JSON representations of the items returned by the commands (e.g., the payloads of [`QueryResult`](crate::query::QueryResult)).
+use crate::graph::TimeZone;
+use crate::query::{NonFinitePolicy, TimeUnit};
+use std::collections::{BTreeMap, BTreeSet};
 
+pub mod csv;
+
+/// Artifact, execution or context type.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Type {
+    /// Type ID.
     pub id: i32,
+
+    /// Type name.
     pub name: String,
+
+    /// Declared properties.
     pub properties: BTreeMap<String, PropertyType>,
+
+    /// Number of the items that set each declared property (only reported with `--usage`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<BTreeMap<String, usize>>,
 }
 
 impl From<mlmd::metadata::ArtifactType> for Type {
@@ -19,6 +32,7 @@ impl From<mlmd::metadata::ArtifactType> for Type {
                 .into_iter()
                 .map(|(k, v)| (k, PropertyType::from(v)))
                 .collect(),
+            usage: None,
         }
     }
 }
@@ -33,6 +47,7 @@ impl From<mlmd::metadata::ExecutionType> for Type {
                 .into_iter()
                 .map(|(k, v)| (k, PropertyType::from(v)))
                 .collect(),
+            usage: None,
         }
     }
 }
@@ -47,15 +62,39 @@ impl From<mlmd::metadata::ContextType> for Type {
                 .into_iter()
                 .map(|(k, v)| (k, PropertyType::from(v)))
                 .collect(),
+            usage: None,
+        }
+    }
+}
+
+impl Type {
+    /// Sets `usage` by counting the declared properties in `keys` (the property names set by each item).
+    pub fn set_usage<'a>(&mut self, keys: impl Iterator<Item = &'a String>) {
+        let mut usage = self
+            .properties
+            .keys()
+            .map(|k| (k.clone(), 0))
+            .collect::<BTreeMap<_, _>>();
+        for k in keys {
+            if let Some(n) = usage.get_mut(k) {
+                *n += 1;
+            }
         }
+        self.usage = Some(usage);
     }
 }
 
+/// Type of a declared property.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum PropertyType {
+    /// 32-bit integer.
     Int,
+
+    /// Double-precision floating point number.
     Double,
+
+    /// String.
     String,
 }
 
@@ -71,27 +110,108 @@ impl From<mlmd::metadata::PropertyType> for PropertyType {
     }
 }
 
+/// Time since the Unix epoch (the unit is chosen by `--time-unit`).
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+pub enum Timestamp {
+    /// Milliseconds.
+    Milliseconds(u64),
+
+    /// Seconds.
+    Seconds(f64),
+}
+
+/// Event relating an artifact and an execution.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Event {
+    /// Artifact ID.
     pub artifact: i32,
+
+    /// Artifact type name.
     pub artifact_type: String,
+
+    /// Execution ID.
     pub execution: i32,
+
+    /// Execution type name.
     pub execution_type: String,
+
+    /// Event type.
     #[serde(rename = "type")]
     pub event_type: EventType,
+
+    /// Path of the artifact within the input or output of the execution.
     pub path: Vec<EventStep>,
-    pub time: f64,
+
+    /// Creation time.
+    pub time: Timestamp,
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+/// Events of the same artifact, execution and type collapsed into one (`--collapse-steps`).
+#[derive(Debug, serde::Serialize)]
+pub struct CollapsedEvent {
+    /// Artifact ID.
+    pub artifact: i32,
+
+    /// Artifact type name.
+    pub artifact_type: String,
+
+    /// Execution ID.
+    pub execution: i32,
+
+    /// Execution type name.
+    pub execution_type: String,
+
+    /// Event type.
+    #[serde(rename = "type")]
+    pub event_type: EventType,
+
+    /// Summarized paths (consecutive trailing indices are shown as ranges such as `"0-2"`).
+    pub steps: Vec<String>,
+
+    /// Number of the collapsed events.
+    pub count: usize,
+
+    /// Creation time of the first collapsed event in the result order.
+    pub time: Timestamp,
+}
+
+/// Event as an edge of the lineage graph (`--format edge-list`).
+#[derive(Debug, serde::Serialize)]
+pub struct EventEdge {
+    /// Source node (e.g., `"1@artifact"` for an input).
+    pub from_id: String,
+
+    /// Destination node (e.g., `"2@execution"` for an input).
+    pub to_id: String,
+
+    /// Event type.
+    pub event_type: EventType,
+}
+
+/// Event type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "UPPERCASE")]
 pub enum EventType {
+    /// `UNKNOWN`.
     Unknown,
+
+    /// `INPUT`.
     Input,
+
+    /// `DECLARED_INPUT`.
     DeclaredInput,
+
+    /// `INTERNAL_INPUT`.
     InternalInput,
+
+    /// `OUTPUT`.
     Output,
+
+    /// `DECLARED_OUTPUT`.
     DeclaredOutput,
+
+    /// `INTERNAL_OUTPUT`.
     InternalOutput,
 }
 
@@ -111,10 +231,28 @@ impl From<mlmd::metadata::EventType> for EventType {
     }
 }
 
+impl From<EventType> for mlmd::metadata::EventType {
+    fn from(x: EventType) -> Self {
+        match x {
+            EventType::Unknown => Self::Unknown,
+            EventType::Input => Self::Input,
+            EventType::DeclaredInput => Self::DeclaredInput,
+            EventType::InternalInput => Self::InternalInput,
+            EventType::Output => Self::Output,
+            EventType::DeclaredOutput => Self::DeclaredOutput,
+            EventType::InternalOutput => Self::InternalOutput,
+        }
+    }
+}
+
+/// Step of an event path.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum EventStep {
+    /// Index of a list.
     Index(i32),
+
+    /// Key of a map.
     Key(String),
 }
 
@@ -138,30 +276,86 @@ impl From<mlmd::metadata::EventStep> for EventStep {
     }
 }
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+impl From<EventStep> for mlmd::metadata::EventStep {
+    fn from(x: EventStep) -> Self {
+        match x {
+            EventStep::Index(x) => Self::Index(x),
+            EventStep::Key(x) => Self::Key(x),
+        }
+    }
+}
+
+/// Artifact.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct Artifact {
+    /// Artifact ID.
     pub id: i32,
+
+    /// Artifact name.
     pub name: Option<String>,
+
+    /// Artifact type name.
     #[serde(rename = "type")]
     pub type_name: String,
+
+    /// Artifact URI.
     pub uri: Option<String>,
+
+    /// Artifact state.
     pub state: ArtifactState,
-    pub ctime: f64,
-    pub mtime: f64,
+
+    /// Creation time.
+    pub ctime: Timestamp,
+
+    /// Last update time.
+    pub mtime: Timestamp,
+
+    /// Properties.
     pub properties: BTreeMap<String, PropertyValue>,
+
+    /// Custom properties.
     pub custom_properties: BTreeMap<String, PropertyValue>,
+
+    /// Names of the properties not declared by the type (only reported with `--report-undeclared-properties`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub undeclared_properties: Option<BTreeSet<String>>,
+
+    /// Difference between the last update time and the creation time (only reported with `--with-mtime-delta`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtime_delta: Option<Timestamp>,
+
+    /// Execution that output the artifact (only reported with `--with-producer-execution`).
+    ///
+    /// `Some(None)` is serialized as `null` (i.e., the producer was requested but there is no such execution).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub producer: Option<Option<ExecutionSummary>>,
+}
+
+/// Brief description of an execution.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ExecutionSummary {
+    /// Execution ID.
+    pub id: i32,
+
+    /// Execution type name.
+    #[serde(rename = "type")]
+    pub type_name: String,
+
+    /// Execution name.
+    pub name: Option<String>,
 }
 
 impl Artifact {
-    pub fn new(type_name: String, x: mlmd::metadata::Artifact) -> Self {
+    /// Makes an [`Artifact`] whose timestamps are represented in `time_unit`.
+    pub fn new(type_name: String, x: mlmd::metadata::Artifact, time_unit: TimeUnit) -> Self {
         Self {
             id: x.id.get(),
             name: x.name,
             type_name,
             uri: x.uri,
             state: x.state.into(),
-            ctime: x.create_time_since_epoch.as_secs_f64(),
-            mtime: x.last_update_time_since_epoch.as_secs_f64(),
+            ctime: time_unit.timestamp(x.create_time_since_epoch),
+            mtime: time_unit.timestamp(x.last_update_time_since_epoch),
             properties: x
                 .properties
                 .into_iter()
@@ -172,10 +366,75 @@ impl Artifact {
                 .into_iter()
                 .map(|(k, v)| (k, v.into()))
                 .collect(),
+            undeclared_properties: None,
+            mtime_delta: None,
+            producer: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactsDiff {
+    pub only_a: Vec<Artifact>,
+    pub only_b: Vec<Artifact>,
+    pub both: Vec<ArtifactPair>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ArtifactPair {
+    pub a: Artifact,
+    pub b: Artifact,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct PropertiesDiff {
+    pub a: i32,
+    pub b: i32,
+    pub properties: PropertyMapDiff,
+    pub custom_properties: PropertyMapDiff,
+}
+
+// `added` and `removed` are the properties only in `b` and only in `a` respectively.
+#[derive(Debug, Default, serde::Serialize)]
+pub struct PropertyMapDiff {
+    pub added: BTreeMap<String, PropertyValue>,
+    pub removed: BTreeMap<String, PropertyValue>,
+    pub changed: BTreeMap<String, PropertyValuePair>,
+}
+
+impl PropertyMapDiff {
+    pub fn new(a: &BTreeMap<String, PropertyValue>, b: &BTreeMap<String, PropertyValue>) -> Self {
+        let mut diff = Self::default();
+        for (name, a_value) in a {
+            match b.get(name) {
+                None => {
+                    diff.removed.insert(name.clone(), a_value.clone());
+                }
+                Some(b_value) if a_value != b_value => {
+                    let pair = PropertyValuePair {
+                        a: a_value.clone(),
+                        b: b_value.clone(),
+                    };
+                    diff.changed.insert(name.clone(), pair);
+                }
+                Some(_) => {}
+            }
+        }
+        for (name, b_value) in b {
+            if !a.contains_key(name) {
+                diff.added.insert(name.clone(), b_value.clone());
+            }
         }
+        diff
     }
 }
 
+#[derive(Debug, serde::Serialize)]
+pub struct PropertyValuePair {
+    pub a: PropertyValue,
+    pub b: PropertyValue,
+}
+
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct ArtifactNode {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -185,7 +444,7 @@ pub struct ArtifactNode {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub uri: Option<String>,
     pub state: ArtifactState,
-    pub time: DateTime<Local>,
+    pub time: String,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub properties: BTreeMap<String, PropertyValue>,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
@@ -193,13 +452,13 @@ pub struct ArtifactNode {
 }
 
 impl ArtifactNode {
-    pub fn new(type_name: String, x: mlmd::metadata::Artifact) -> Self {
+    pub fn new(type_name: String, x: mlmd::metadata::Artifact, timezone: TimeZone) -> Self {
         Self {
             name: x.name,
             type_name,
             uri: x.uri,
             state: x.state.into(),
-            time: DateTime::from(SystemTime::UNIX_EPOCH + x.last_update_time_since_epoch),
+            time: timezone.format(x.last_update_time_since_epoch),
             properties: x
                 .properties
                 .into_iter()
@@ -214,16 +473,51 @@ impl ArtifactNode {
     }
 }
 
+/// Artifact state.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ArtifactState {
+    /// `UNKNOWN`.
     Unknown,
+
+    /// `PENDING`.
     Pending,
+
+    /// `LIVE`.
     Live,
+
+    /// `MARKED_FOR_DELETION`.
     MarkedForDeletion,
+
+    /// `DELETED`.
     Deleted,
 }
 
+impl ArtifactState {
+    pub(crate) const POSSIBLE_VALUES: &'static [&'static str] = &[
+        "UNKNOWN",
+        "PENDING",
+        "LIVE",
+        "MARKED_FOR_DELETION",
+        "DELETED",
+    ];
+}
+
+impl std::str::FromStr for ArtifactState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s.to_ascii_uppercase().as_str() {
+            "UNKNOWN" => Ok(Self::Unknown),
+            "PENDING" => Ok(Self::Pending),
+            "LIVE" => Ok(Self::Live),
+            "MARKED_FOR_DELETION" => Ok(Self::MarkedForDeletion),
+            "DELETED" => Ok(Self::Deleted),
+            _ => anyhow::bail!("unknown artifact state: {:?}", s),
+        }
+    }
+}
+
 impl From<mlmd::metadata::ArtifactState> for ArtifactState {
     fn from(x: mlmd::metadata::ArtifactState) -> Self {
         use mlmd::metadata::ArtifactState::*;
@@ -238,14 +532,116 @@ impl From<mlmd::metadata::ArtifactState> for ArtifactState {
     }
 }
 
+impl From<ArtifactState> for mlmd::metadata::ArtifactState {
+    fn from(x: ArtifactState) -> Self {
+        match x {
+            ArtifactState::Unknown => Self::Unknown,
+            ArtifactState::Pending => Self::Pending,
+            ArtifactState::Live => Self::Live,
+            ArtifactState::MarkedForDeletion => Self::MarkedForDeletion,
+            ArtifactState::Deleted => Self::Deleted,
+        }
+    }
+}
+
+/// Property value.
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 #[serde(untagged)]
 pub enum PropertyValue {
+    /// 32-bit integer.
     Int(i32),
+
+    /// Double-precision floating point number.
     Double(f64),
+
+    /// String.
     String(String),
 }
 
+impl PropertyValue {
+    /// Converts this value to `ty` if the conversion does not lose information.
+    pub fn coerce(&self, ty: &PropertyType) -> Option<Self> {
+        match (self, ty) {
+            (Self::Int(x), PropertyType::Int) => Some(Self::Int(*x)),
+            (Self::Int(x), PropertyType::Double) => Some(Self::Double(f64::from(*x))),
+            (Self::Int(x), PropertyType::String) => Some(Self::String(x.to_string())),
+            (Self::Double(x), PropertyType::Int) => {
+                if x.fract() == 0.0 && *x >= f64::from(i32::MIN) && *x <= f64::from(i32::MAX) {
+                    Some(Self::Int(*x as i32))
+                } else {
+                    None
+                }
+            }
+            (Self::Double(x), PropertyType::Double) => Some(Self::Double(*x)),
+            (Self::Double(x), PropertyType::String) => Some(Self::String(x.to_string())),
+            (Self::String(x), PropertyType::Int) => x.parse().ok().map(Self::Int),
+            (Self::String(x), PropertyType::Double) => x.parse().ok().map(Self::Double),
+            (Self::String(x), PropertyType::String) => Some(Self::String(x.clone())),
+        }
+    }
+}
+
+// Returns the name of a state as it appears in the JSON output (e.g., "LIVE").
+pub fn state_name<T: serde::Serialize>(state: T) -> String {
+    match serde_json::to_value(state) {
+        Ok(serde_json::Value::String(x)) => x,
+        _ => String::new(),
+    }
+}
+
+pub fn coerce_properties(
+    properties: &mut BTreeMap<String, PropertyValue>,
+    schema: &BTreeMap<String, PropertyType>,
+    strict: bool,
+) -> anyhow::Result<()> {
+    for (name, value) in properties.iter_mut() {
+        if let Some(ty) = schema.get(name) {
+            if let Some(x) = value.coerce(ty) {
+                *value = x;
+            } else if strict {
+                anyhow::bail!(
+                    "the value {:?} of the property {:?} cannot be converted to the declared type {:?}",
+                    value,
+                    name,
+                    ty
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+// Applies `policy` to the non-finite double values in `properties`
+// (`null` is the default representation of such values in JSON).
+pub fn handle_nonfinite(
+    properties: &mut BTreeMap<String, PropertyValue>,
+    policy: NonFinitePolicy,
+) -> anyhow::Result<()> {
+    for (name, value) in properties.iter_mut() {
+        if let PropertyValue::Double(x) = value {
+            if x.is_finite() {
+                continue;
+            }
+            match policy {
+                NonFinitePolicy::Null => {}
+                NonFinitePolicy::String => {
+                    *value = PropertyValue::String(if x.is_nan() {
+                        "NaN".to_owned()
+                    } else if x.is_sign_positive() {
+                        "Infinity".to_owned()
+                    } else {
+                        "-Infinity".to_owned()
+                    });
+                }
+                NonFinitePolicy::Error => {
+                    anyhow::bail!("the property {:?} has a non-finite value ({})", name, x);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 impl From<mlmd::metadata::PropertyValue> for PropertyValue {
     fn from(x: mlmd::metadata::PropertyValue) -> Self {
         use mlmd::metadata::PropertyValue::*;
@@ -258,17 +654,53 @@ impl From<mlmd::metadata::PropertyValue> for PropertyValue {
     }
 }
 
+impl From<PropertyValue> for mlmd::metadata::PropertyValue {
+    fn from(x: PropertyValue) -> Self {
+        match x {
+            PropertyValue::Int(x) => Self::Int(x),
+            PropertyValue::Double(x) => Self::Double(x),
+            PropertyValue::String(x) => Self::String(x),
+        }
+    }
+}
+
+/// Execution.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Execution {
+    /// Execution ID.
     pub id: i32,
+
+    /// Execution name.
     pub name: Option<String>,
+
+    /// Execution type name.
     #[serde(rename = "type")]
     pub type_name: String,
+
+    /// Last known state.
     pub state: ExecutionState,
-    pub ctime: f64,
-    pub mtime: f64,
+
+    /// Creation time.
+    pub ctime: Timestamp,
+
+    /// Last update time.
+    pub mtime: Timestamp,
+
+    /// Properties.
     pub properties: BTreeMap<String, PropertyValue>,
+
+    /// Custom properties.
     pub custom_properties: BTreeMap<String, PropertyValue>,
+
+    /// Input artifacts (only reported with `--failed-inputs`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inputs: Option<Vec<Artifact>>,
+
+    /// Time from the first input event to the last output event (only reported with `--with-duration`).
+    ///
+    /// `Some(None)` is serialized as `null` (i.e., the duration was requested but is unknown).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration_seconds: Option<Option<f64>>,
 }
 
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
@@ -278,7 +710,7 @@ pub struct ExecutionNode {
     #[serde(rename = "type")]
     pub type_name: String,
     pub state: ExecutionState,
-    pub time: DateTime<Local>,
+    pub time: String,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
     pub properties: BTreeMap<String, PropertyValue>,
     #[serde(skip_serializing_if = "BTreeMap::is_empty")]
@@ -286,12 +718,12 @@ pub struct ExecutionNode {
 }
 
 impl ExecutionNode {
-    pub fn new(type_name: String, x: mlmd::metadata::Execution) -> Self {
+    pub fn new(type_name: String, x: mlmd::metadata::Execution, timezone: TimeZone) -> Self {
         Self {
             name: x.name,
             type_name,
             state: x.last_known_state.into(),
-            time: DateTime::from(SystemTime::UNIX_EPOCH + x.last_update_time_since_epoch),
+            time: timezone.format(x.last_update_time_since_epoch),
             properties: x
                 .properties
                 .into_iter()
@@ -306,15 +738,29 @@ impl ExecutionNode {
     }
 }
 
+/// Execution state.
 #[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum ExecutionState {
+    /// `UNKNOWN`.
     Unknown,
+
+    /// `NEW`.
     New,
+
+    /// `RUNNING`.
     Running,
+
+    /// `COMPLETE`.
     Complete,
+
+    /// `FAILED`.
     Failed,
+
+    /// `CACHED`.
     Cached,
+
+    /// `CANCELED`.
     Canceled,
 }
 
@@ -334,14 +780,52 @@ impl From<mlmd::metadata::ExecutionState> for ExecutionState {
     }
 }
 
+impl From<ExecutionState> for mlmd::metadata::ExecutionState {
+    fn from(x: ExecutionState) -> Self {
+        match x {
+            ExecutionState::Unknown => Self::Unknown,
+            ExecutionState::New => Self::New,
+            ExecutionState::Running => Self::Running,
+            ExecutionState::Complete => Self::Complete,
+            ExecutionState::Failed => Self::Failed,
+            ExecutionState::Cached => Self::Cached,
+            ExecutionState::Canceled => Self::Canceled,
+        }
+    }
+}
+
+/// Context.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct Context {
+    /// Context ID.
     pub id: i32,
+
+    /// Context name.
     pub name: String,
+
+    /// Context type name.
     #[serde(rename = "type")]
     pub type_name: String,
-    pub ctime: f64,
-    pub mtime: f64,
+
+    /// Creation time.
+    pub ctime: Timestamp,
+
+    /// Last update time.
+    pub mtime: Timestamp,
+
+    /// Properties.
     pub properties: BTreeMap<String, PropertyValue>,
+
+    /// Custom properties.
     pub custom_properties: BTreeMap<String, PropertyValue>,
+
+    /// Time of the latest event relating to the members (only reported with `--with-latest-event`).
+    ///
+    /// `Some(None)` is serialized as `null` (i.e., requested but no events relate to the context).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_event_time: Option<Option<Timestamp>>,
+
+    /// Type of the latest event relating to the members (only reported with `--with-latest-event`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_event_type: Option<Option<EventType>>,
 }
@@ -0,0 +1,156 @@
+//! CSV serialization of search results.
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::Write;
+
+/// Writes `value` (a JSON representation of a search result) as CSV.
+///
+/// An array of objects becomes one row per object. Nested objects (e.g., `properties`) are
+/// flattened into `<field><key_separator><key>` columns, and the header is the union of the columns of all
+/// rows in order of their first appearance. Other values are written as a single cell.
+/// To keep the column names unambiguous, `\` and `key_separator` in a key are escaped with `\`
+/// (e.g., the `a.b` key of `properties` becomes the `properties.a\.b` column).
+///
+/// `null` values and the columns missing in a row are written as `null_as`.
+pub fn write<W: Write>(
+    mut writer: W,
+    value: &Value,
+    null_as: &str,
+    key_separator: &str,
+) -> anyhow::Result<()> {
+    let rows = match value {
+        Value::Array(items) => items
+            .iter()
+            .map(|x| flatten(x, key_separator))
+            .collect::<Vec<_>>(),
+        Value::Object(_) => vec![flatten(value, key_separator)],
+        _ => {
+            writeln!(
+                writer,
+                "{}",
+                escape(cell(value).as_deref().unwrap_or(null_as))
+            )?;
+            return Ok(());
+        }
+    };
+
+    let mut header = Vec::new();
+    let mut seen = HashSet::new();
+    for row in &rows {
+        for (column, _) in row {
+            if seen.insert(column.clone()) {
+                header.push(column.clone());
+            }
+        }
+    }
+
+    write_record(&mut writer, header.iter().map(String::as_str))?;
+    for row in &rows {
+        let cells = header.iter().map(|column| {
+            row.iter()
+                .find(|(c, _)| c == column)
+                .and_then(|(_, v)| v.as_deref())
+                .unwrap_or(null_as)
+        });
+        write_record(&mut writer, cells)?;
+    }
+    Ok(())
+}
+
+fn write_record<'a, W: Write>(
+    writer: &mut W,
+    cells: impl Iterator<Item = &'a str>,
+) -> anyhow::Result<()> {
+    let line = cells.map(escape).collect::<Vec<_>>().join(",");
+    writeln!(writer, "{}", line)?;
+    Ok(())
+}
+
+fn flatten(value: &Value, key_separator: &str) -> Vec<(String, Option<String>)> {
+    let mut row = Vec::new();
+    if let Value::Object(fields) = value {
+        for (name, value) in fields {
+            if let Value::Object(nested) = value {
+                for (key, value) in nested {
+                    let key = escape_key(key, key_separator);
+                    row.push((format!("{}{}{}", name, key_separator, key), cell(value)));
+                }
+            } else {
+                row.push((name.clone(), cell(value)));
+            }
+        }
+    } else {
+        row.push(("value".to_owned(), cell(value)));
+    }
+    row
+}
+
+fn escape_key(key: &str, separator: &str) -> String {
+    let key = key.replace('\\', "\\\\");
+    if separator.is_empty() {
+        key
+    } else {
+        key.replace(separator, &format!("\\{}", separator))
+    }
+}
+
+// Returns `None` for `null`.
+fn cell(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => None,
+        Value::String(x) => Some(x.clone()),
+        _ => Some(value.to_string()),
+    }
+}
+
+fn escape(cell: &str) -> String {
+    if cell.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn to_csv(value: &Value, null_as: &str) -> String {
+        let mut buf = Vec::new();
+        write(&mut buf, value, null_as, ".").unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn header_is_union_of_columns() {
+        let value = json!([
+            {"id": 1, "properties": {"a": 1}},
+            {"id": 2, "uri": "x", "properties": {"b": "y"}}
+        ]);
+        assert_eq!(
+            to_csv(&value, ""),
+            "id,properties.a,properties.b,uri\n1,1,,\n2,,y,x\n"
+        );
+    }
+
+    #[test]
+    fn special_characters_are_quoted() {
+        let value = json!([{"a": "x,y", "b": "say \"hi\"", "c": "line1\nline2"}]);
+        assert_eq!(
+            to_csv(&value, ""),
+            "a,b,c\n\"x,y\",\"say \"\"hi\"\"\",\"line1\nline2\"\n"
+        );
+    }
+
+    #[test]
+    fn null_as_is_used_for_null_and_missing_values() {
+        let value = json!([{"a": null, "b": 1}, {"b": 2}]);
+        assert_eq!(to_csv(&value, "NA"), "a,b\nNA,1\nNA,2\n");
+    }
+
+    #[test]
+    fn count_is_single_cell() {
+        assert_eq!(to_csv(&json!(42), ""), "42\n");
+    }
+}
@@ -0,0 +1,141 @@
+//! `$ mlmdquery validate` implementation.
+//!
+//! Note that dangling context members (attributions and associations referring to nonexistent
+//! artifacts or executions) are not reported because the `mlmd` API only exposes existing members.
+use crate::db::{fetch_all_pages, DbOpt, PAGE_SIZE};
+use mlmd::metadata::{ArtifactId, ExecutionId, TypeId};
+use mlmd::requests::EventOrderByField;
+use std::collections::BTreeSet;
+
+/// `$ mlmdquery validate` options.
+#[derive(Debug, Clone, structopt::StructOpt)]
+#[structopt(rename_all = "kebab-case")]
+pub struct ValidateOpt {
+    /// Database options.
+    #[structopt(flatten)]
+    pub db: DbOpt,
+}
+
+/// Integrity problem found by `$ mlmdquery validate`.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+#[allow(missing_docs)]
+pub enum Issue {
+    /// An event refers to a nonexistent artifact.
+    EventWithoutArtifact { artifact: i32, execution: i32 },
+
+    /// An event refers to a nonexistent execution.
+    EventWithoutExecution { artifact: i32, execution: i32 },
+
+    /// An artifact has a nonexistent type.
+    ArtifactWithoutType { artifact: i32, type_id: i32 },
+
+    /// An execution has a nonexistent type.
+    ExecutionWithoutType { execution: i32, type_id: i32 },
+
+    /// A context has a nonexistent type.
+    ContextWithoutType { context: i32, type_id: i32 },
+}
+
+impl ValidateOpt {
+    /// Example invocations shown at the end of the help message.
+    pub const EXAMPLES: &'static str = concat!(
+        "EXAMPLES:\n",
+        "    $ mlmdquery validate || echo 'integrity problems were found'\n",
+    );
+
+    /// `$ mlmdquery validate` implementation.
+    pub async fn validate(&self) -> anyhow::Result<Vec<Issue>> {
+        let mut store = self.db.connect().await?;
+        let mut issues = Vec::new();
+
+        let artifacts = fetch_all_pages(&mut store, 0, 0, |store, limit, offset| {
+            let request = store.get_artifacts().limit(limit).offset(offset);
+            Box::pin(async move { Ok(request.execute().await?) })
+        })
+        .await?;
+        let executions = fetch_all_pages(&mut store, 0, 0, |store, limit, offset| {
+            let request = store.get_executions().limit(limit).offset(offset);
+            Box::pin(async move { Ok(request.execute().await?) })
+        })
+        .await?;
+        let contexts = fetch_all_pages(&mut store, 0, 0, |store, limit, offset| {
+            let request = store.get_contexts().limit(limit).offset(offset);
+            Box::pin(async move { Ok(request.execute().await?) })
+        })
+        .await?;
+
+        let artifact_types = type_ids(store.get_artifact_types().execute().await?, |x| x.id);
+        for x in &artifacts {
+            if !artifact_types.contains(&x.type_id) {
+                issues.push(Issue::ArtifactWithoutType {
+                    artifact: x.id.get(),
+                    type_id: x.type_id.get(),
+                });
+            }
+        }
+        let execution_types = type_ids(store.get_execution_types().execute().await?, |x| x.id);
+        for x in &executions {
+            if !execution_types.contains(&x.type_id) {
+                issues.push(Issue::ExecutionWithoutType {
+                    execution: x.id.get(),
+                    type_id: x.type_id.get(),
+                });
+            }
+        }
+        let context_types = type_ids(store.get_context_types().execute().await?, |x| x.id);
+        for x in &contexts {
+            if !context_types.contains(&x.type_id) {
+                issues.push(Issue::ContextWithoutType {
+                    context: x.id.get(),
+                    type_id: x.type_id.get(),
+                });
+            }
+        }
+
+        let artifact_ids = artifacts
+            .iter()
+            .map(|x| x.id)
+            .collect::<BTreeSet<ArtifactId>>();
+        let execution_ids = executions
+            .iter()
+            .map(|x| x.id)
+            .collect::<BTreeSet<ExecutionId>>();
+        let mut offset = 0;
+        loop {
+            let events = store
+                .get_events()
+                .limit(PAGE_SIZE)
+                .offset(offset)
+                .order_by(EventOrderByField::CreateTime, true)
+                .execute()
+                .await?;
+            offset += events.len();
+            let done = events.len() < PAGE_SIZE;
+            for event in events {
+                let artifact = event.artifact_id.get();
+                let execution = event.execution_id.get();
+                if !artifact_ids.contains(&event.artifact_id) {
+                    issues.push(Issue::EventWithoutArtifact {
+                        artifact,
+                        execution,
+                    });
+                }
+                if !execution_ids.contains(&event.execution_id) {
+                    issues.push(Issue::EventWithoutExecution {
+                        artifact,
+                        execution,
+                    });
+                }
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+}
+
+fn type_ids<T>(types: Vec<T>, id: impl Fn(&T) -> TypeId) -> BTreeSet<TypeId> {
+    types.iter().map(id).collect()
+}